@@ -0,0 +1,569 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::nucc_binary_parsed::calc_crc32;
+use crate::utils::fixed_string_bytes;
+use crate::{
+    CharaCode, CharaCodeEntry, CharaCodeError, EvFile, MessageInfo, MessageInfoEntry,
+    NuccBinaryParsed, NuccBinaryParsedSet, PlayerColorEntry, PlayerColorParam, PrmLoad,
+    SoundTestEntry, SoundTestParam,
+};
+
+/// An entry of a table, along with the file it was found in and its position.
+#[derive(Serialize)]
+pub struct CharacterEntry<'a, T> {
+    pub file: &'a str,
+    pub index: usize,
+    pub entry: &'a T,
+}
+
+/// Everything in a set of files that belongs to one character.
+#[derive(Serialize)]
+pub struct CharacterEntries<'a> {
+    pub code: String,
+    pub chara_codes: Vec<CharacterEntry<'a, CharaCodeEntry>>,
+    pub colors: Vec<CharacterEntry<'a, PlayerColorEntry>>,
+
+    /// Sound test entries whose `char_name` is the character code
+    pub sound_tests: Vec<CharacterEntry<'a, SoundTestEntry>>,
+
+    /// Messages for the name and description IDs of the character's sound test entries
+    pub messages: Vec<CharacterEntry<'a, MessageInfoEntry>>,
+
+    /// Paths of the files that belong to the character as a whole (`prm_load.bin` and `_ev.bin`),
+    /// found by their file name starting with the character code
+    pub files: Vec<&'a str>,
+}
+
+impl CharacterEntries<'_> {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+/// A string that was changed by a character code rename.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CharacterRename {
+    /// Path of the file containing the string
+    pub file: String,
+
+    /// JSON path of the field, relative to the file, or `path` for the path of the file itself
+    pub location: String,
+
+    pub old: String,
+    pub new: String,
+}
+
+impl fmt::Display for CharacterRename {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: `{}` -> `{}`",
+            self.file, self.location, self.old, self.new
+        )
+    }
+}
+
+/// Pairs of (old, new) character codes, applied all at once so that codes can be swapped.
+struct CodeMapping<'a>(Vec<(&'a str, &'a str)>);
+
+impl CodeMapping<'_> {
+    /// New code of a field that holds a whole character code.
+    fn code(&self, code: &str) -> Option<String> {
+        self.0
+            .iter()
+            .find(|(from, _)| *from == code)
+            .map(|(_, to)| to.to_string())
+    }
+
+    /// Replaces the codes within a string, such as a path. A code only matches at the start of the
+    /// string or after `/`, `\` or `_` (`1dio01_ev/1dio01_ev.bin`, `spc/2nrtbod1.xfbin`).
+    fn text(&self, text: &str) -> String {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        let mut at_start = true;
+
+        'outer: while let Some(c) = rest.chars().next() {
+            if at_start {
+                for (from, to) in self.0.iter() {
+                    if let Some(after) = rest.strip_prefix(from) {
+                        result.push_str(to);
+                        rest = after;
+                        at_start = false;
+                        continue 'outer;
+                    }
+                }
+            }
+
+            result.push(c);
+            rest = &rest[c.len_utf8()..];
+            at_start = matches!(c, '/' | '\\' | '_');
+        }
+
+        result
+    }
+}
+
+/// Records a change to `value`, if `renamed` is different.
+fn rename(
+    renames: &mut Vec<CharacterRename>,
+    file: &str,
+    location: String,
+    value: &mut String,
+    renamed: Option<String>,
+) {
+    if let Some(renamed) = renamed.filter(|renamed| renamed != value) {
+        renames.push(CharacterRename {
+            file: file.to_string(),
+            location,
+            old: std::mem::replace(value, renamed.clone()),
+            new: renamed,
+        });
+    }
+}
+
+fn entries_of<'a, T: NuccBinaryParsed, E>(
+    set: &'a NuccBinaryParsedSet,
+    entries: impl Fn(&'a T) -> &'a [E],
+    belongs: impl Fn(&E) -> bool,
+) -> Vec<CharacterEntry<'a, E>> {
+    set.files_of::<T>()
+        .flat_map(|(file, parsed)| {
+            entries(parsed)
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| belongs(entry))
+                .map(move |(index, entry)| CharacterEntry { file, index, entry })
+        })
+        .collect()
+}
+
+/// Whether the file name is exactly `<code>prm_load.bin` or `<code>_ev.bin`. Codes are matched
+/// case-sensitively, the same way they are renamed.
+fn is_character_file(path: &str, code: &str) -> bool {
+    PrmLoad::chara_code_of_path(path) == Some(code)
+        || EvFile::chara_code_of_path(path) == Some(code)
+}
+
+/// New path and code of a file that belongs to a renamed character as a whole.
+fn renamed_path<'a>(path: &str, mapping: &CodeMapping<'a>) -> Option<(String, &'a str)> {
+    mapping
+        .0
+        .iter()
+        .find(|(from, _)| is_character_file(path, from))
+        .map(|(_, to)| (mapping.text(path), *to))
+}
+
+fn check_prm_load(
+    path: &str,
+    prm_load: &PrmLoad,
+    mapping: &CodeMapping,
+) -> Result<(), CharaCodeError> {
+    for (i, entry) in prm_load.entries.iter().enumerate() {
+        for (field, name) in [
+            ("folder_name", &entry.folder_name.string),
+            ("file_name", &entry.file_name.string),
+        ] {
+            fixed_string_bytes(
+                &format!("{}: entries[{}].{}", path, i, field),
+                &mapping.text(name),
+                0x20,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn check_ev(path: &str, ev_file: &EvFile, mapping: &CodeMapping) -> Result<(), CharaCodeError> {
+    for (i, entry) in ev_file.entries.iter().enumerate() {
+        fixed_string_bytes(
+            &format!("{}: entries[{}].xfbin_path", path, i),
+            &mapping.text(&entry.xfbin_path),
+            0x20,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn rename_prm_load(
+    renames: &mut Vec<CharacterRename>,
+    path: &str,
+    prm_load: &mut PrmLoad,
+    mapping: &CodeMapping,
+) {
+    for (i, entry) in prm_load.entries.iter_mut().enumerate() {
+        for (field, name) in [
+            ("folder_name", &mut entry.folder_name.string),
+            ("file_name", &mut entry.file_name.string),
+        ] {
+            let renamed = Some(mapping.text(name));
+            rename(
+                renames,
+                path,
+                format!("entries[{}].{}", i, field),
+                name,
+                renamed,
+            );
+        }
+    }
+}
+
+fn rename_ev(
+    renames: &mut Vec<CharacterRename>,
+    path: &str,
+    ev_file: &mut EvFile,
+    mapping: &CodeMapping,
+) {
+    for (i, entry) in ev_file.entries.iter_mut().enumerate() {
+        let renamed = Some(mapping.text(&entry.xfbin_path));
+        rename(
+            renames,
+            path,
+            format!("entries[{}].xfbin_path", i),
+            &mut entry.xfbin_path,
+            renamed,
+        );
+    }
+}
+
+impl NuccBinaryParsedSet {
+    pub fn character(&self, code: &str) -> CharacterEntries<'_> {
+        let sound_tests = entries_of(
+            self,
+            |param: &SoundTestParam| &param.entries,
+            |entry| entry.char_name == code,
+        );
+
+        let message_ids: Vec<Vec<u8>> = sound_tests
+            .iter()
+            .flat_map(|sound_test| [&sound_test.entry.name_id, &sound_test.entry.desc_id])
+            .filter(|msg_id| !msg_id.is_empty())
+            .map(|msg_id| calc_crc32(msg_id.as_bytes()))
+            .collect();
+
+        CharacterEntries {
+            code: code.to_string(),
+            chara_codes: entries_of(
+                self,
+                |chara_code: &CharaCode| &chara_code.entries,
+                |entry| entry.chara.string == code,
+            ),
+            colors: entries_of(
+                self,
+                |param: &PlayerColorParam| &param.entries,
+                |entry| entry.char_code == code,
+            ),
+            messages: entries_of(
+                self,
+                |message_info: &MessageInfo| &message_info.entries,
+                |entry| message_ids.contains(&entry.msg_id_crc32),
+            ),
+            sound_tests,
+            files: self
+                .files_of::<PrmLoad>()
+                .map(|(path, _)| path)
+                .chain(self.files_of::<EvFile>().map(|(path, _)| path))
+                .filter(|path| is_character_file(path, code))
+                .collect(),
+        }
+    }
+
+    /// Whether the code is used by a character code or player color entry, or has whole files.
+    fn has_character(&self, code: &str) -> bool {
+        self.files_of::<CharaCode>()
+            .any(|(_, chara_code)| chara_code.find_by_code(code).is_some())
+            || self
+                .files_of::<PlayerColorParam>()
+                .any(|(_, param)| !param.colors_of(code).is_empty())
+            || self
+                .files
+                .iter()
+                .any(|(path, _)| is_character_file(path, code))
+    }
+
+    /// Copies everything that belongs to `code` under `new_code`, to bootstrap a new character slot.
+    /// Strings containing the old code (message IDs, `prm_load` names and `_ev` xfbin paths in
+    /// whole files) get the new code instead, where it starts the string or follows `/`, `\` or `_`.
+    pub fn clone_character(&mut self, code: &str, new_code: &str) -> Result<(), CharaCodeError> {
+        if self.has_character(new_code) {
+            return Err(CharaCodeError::DuplicateCode(new_code.to_string()));
+        }
+
+        // Check everything that can fail before changing anything, so a failed clone leaves no changes behind
+        let mapping = CodeMapping(vec![(code, new_code)]);
+        for (path, chara_code) in self.files_of::<CharaCode>() {
+            if chara_code.find_by_code(code).is_some() {
+                fixed_string_bytes(
+                    &format!("{}: entries[{}].chara", path, chara_code.entries.len()),
+                    new_code,
+                    8,
+                )?;
+                chara_code.next_index()?;
+            }
+        }
+
+        for (path, parsed) in self.files.iter() {
+            if !is_character_file(path, code) {
+                continue;
+            }
+
+            let new_path = mapping.text(path);
+            if self.files.iter().any(|(other, _)| *other == new_path) {
+                return Err(CharaCodeError::DuplicateCode(new_code.to_string()));
+            }
+
+            if let Some(prm_load) = parsed.downcast_ref::<PrmLoad>() {
+                check_prm_load(path, prm_load, &mapping)?;
+            } else if let Some(ev_file) = parsed.downcast_ref::<EvFile>() {
+                check_ev(path, ev_file, &mapping)?;
+            }
+        }
+
+        for (_, chara_code) in self.files_of_mut::<CharaCode>() {
+            if chara_code.find_by_code(code).is_some() {
+                chara_code.add_character(new_code)?;
+            }
+        }
+
+        for (_, param) in self.files_of_mut::<PlayerColorParam>() {
+            let colors: Vec<PlayerColorEntry> = param
+                .entries
+                .iter()
+                .filter(|entry| entry.char_code == code)
+                .map(|entry| PlayerColorEntry {
+                    char_code_pointer: 0,
+                    char_code: new_code.to_string(),
+                    costume_index: entry.costume_index,
+                    rgb: entry.rgb,
+                })
+                .collect();
+
+            param.entries.extend(colors);
+        }
+
+        // Old and new message ID CRCs, for copying the messages of the sound test entries
+        let mut renamed_ids = Vec::new();
+
+        for (_, param) in self.files_of_mut::<SoundTestParam>() {
+            let sound_tests: Vec<SoundTestEntry> = param
+                .entries
+                .iter()
+                .filter(|entry| entry.char_name == code)
+                .map(|entry| {
+                    let mut clone = entry.clone();
+                    clone.char_name = new_code.to_string();
+
+                    for msg_id in [&mut clone.name_id, &mut clone.desc_id] {
+                        let renamed = mapping.text(msg_id);
+                        if !msg_id.is_empty() && renamed != *msg_id {
+                            renamed_ids.push((
+                                calc_crc32(msg_id.as_bytes()),
+                                calc_crc32(renamed.as_bytes()),
+                            ));
+                        }
+
+                        *msg_id = renamed;
+                    }
+
+                    clone.name_id_crc32_no_edit = calc_crc32(clone.name_id.as_bytes());
+                    clone.desc_id_crc32_no_edit = calc_crc32(clone.desc_id.as_bytes());
+                    clone
+                })
+                .collect();
+
+            param.entries.extend(sound_tests);
+        }
+
+        for (_, message_info) in self.files_of_mut::<MessageInfo>() {
+            let messages: Vec<MessageInfoEntry> = message_info
+                .entries
+                .iter()
+                .filter_map(|entry| {
+                    let (_, renamed) = renamed_ids
+                        .iter()
+                        .find(|(original, _)| *original == entry.msg_id_crc32)?;
+
+                    let mut clone = entry.clone();
+                    clone.msg_id_crc32 = renamed.clone();
+                    Some(clone)
+                })
+                .collect();
+
+            message_info.entries.extend(messages);
+        }
+
+        let mut files: Vec<(String, Box<dyn NuccBinaryParsed>)> = Vec::new();
+        for (path, parsed) in self.files.iter() {
+            if !is_character_file(path, code) {
+                continue;
+            }
+
+            let new_path = mapping.text(path);
+            if let Some(prm_load) = parsed.downcast_ref::<PrmLoad>() {
+                let mut clone = prm_load.clone();
+                rename_prm_load(&mut Vec::new(), &new_path, &mut clone, &mapping);
+                files.push((new_path, Box::new(clone)));
+            } else if let Some(ev_file) = parsed.downcast_ref::<EvFile>() {
+                let mut clone = ev_file.clone();
+                rename_ev(&mut Vec::new(), &new_path, &mut clone, &mapping);
+                files.push((new_path, Box::new(clone)));
+            }
+        }
+
+        for (path, parsed) in files {
+            self.add(&path, parsed);
+        }
+
+        Ok(())
+    }
+
+    /// Renames a character code in every file: character codes, player colors, sound tests,
+    /// `prm_load` folder and file names and `_ev` xfbin paths, along with the paths of the
+    /// character's whole files. Returns every change made.
+    pub fn rename_character(
+        &mut self,
+        code: &str,
+        new_code: &str,
+    ) -> Result<Vec<CharacterRename>, CharaCodeError> {
+        if code == new_code {
+            return Ok(Vec::new());
+        }
+
+        if self.has_character(new_code) {
+            return Err(CharaCodeError::DuplicateCode(new_code.to_string()));
+        }
+
+        self.rename_codes(&CodeMapping(vec![(code, new_code)]))
+    }
+
+    /// Swaps two character codes in every file, like `rename_character`.
+    pub fn swap_characters(
+        &mut self,
+        code: &str,
+        other_code: &str,
+    ) -> Result<Vec<CharacterRename>, CharaCodeError> {
+        for code in [code, other_code] {
+            if !self.has_character(code) {
+                return Err(CharaCodeError::UnknownCode(code.to_string()));
+            }
+        }
+
+        self.rename_codes(&CodeMapping(vec![(code, other_code), (other_code, code)]))
+    }
+
+    fn rename_codes(
+        &mut self,
+        mapping: &CodeMapping,
+    ) -> Result<Vec<CharacterRename>, CharaCodeError> {
+        // Check fixed-size names before changing anything, so a failed rename leaves no changes behind
+        for (path, chara_code) in self.files_of::<CharaCode>() {
+            for (i, entry) in chara_code.entries.iter().enumerate() {
+                if let Some(renamed) = mapping.code(&entry.chara.string) {
+                    fixed_string_bytes(&format!("{}: entries[{}].chara", path, i), &renamed, 8)?;
+                }
+            }
+        }
+
+        for (path, prm_load) in self.files_of::<PrmLoad>() {
+            check_prm_load(path, prm_load, mapping)?;
+        }
+
+        for (path, ev_file) in self.files_of::<EvFile>() {
+            check_ev(path, ev_file, mapping)?;
+        }
+
+        let paths: Vec<Option<(String, &str)>> = self
+            .files
+            .iter()
+            .map(|(path, _)| renamed_path(path, mapping))
+            .collect();
+
+        // Whole files of the character are moved along with its code, so no path may be taken twice
+        let final_paths: Vec<&str> = self
+            .files
+            .iter()
+            .zip(paths.iter())
+            .map(|((path, _), renamed)| {
+                renamed
+                    .as_ref()
+                    .map_or(path.as_str(), |(path, _)| path.as_str())
+            })
+            .collect();
+
+        for (i, renamed) in paths.iter().enumerate() {
+            if let Some((new_path, new_code)) = renamed {
+                if final_paths
+                    .iter()
+                    .enumerate()
+                    .any(|(j, path)| j != i && *path == new_path.as_str())
+                {
+                    return Err(CharaCodeError::DuplicateCode(new_code.to_string()));
+                }
+            }
+        }
+
+        let mut renames = Vec::new();
+
+        for (path, chara_code) in self.files_of_mut::<CharaCode>() {
+            for (i, entry) in chara_code.entries.iter_mut().enumerate() {
+                let renamed = mapping.code(&entry.chara.string);
+                rename(
+                    &mut renames,
+                    path,
+                    format!("entries[{}].chara", i),
+                    &mut entry.chara.string,
+                    renamed,
+                );
+            }
+        }
+
+        for (path, param) in self.files_of_mut::<PlayerColorParam>() {
+            for (i, entry) in param.entries.iter_mut().enumerate() {
+                let renamed = mapping.code(&entry.char_code);
+                rename(
+                    &mut renames,
+                    path,
+                    format!("entries[{}].char_code", i),
+                    &mut entry.char_code,
+                    renamed,
+                );
+            }
+        }
+
+        for (path, param) in self.files_of_mut::<SoundTestParam>() {
+            for (i, entry) in param.entries.iter_mut().enumerate() {
+                let renamed = mapping.code(&entry.char_name);
+                rename(
+                    &mut renames,
+                    path,
+                    format!("entries[{}].char_name", i),
+                    &mut entry.char_name,
+                    renamed,
+                );
+            }
+        }
+
+        for (path, prm_load) in self.files_of_mut::<PrmLoad>() {
+            rename_prm_load(&mut renames, path, prm_load, mapping);
+        }
+
+        for (path, ev_file) in self.files_of_mut::<EvFile>() {
+            rename_ev(&mut renames, path, ev_file, mapping);
+        }
+
+        for ((path, _), renamed) in self.files.iter_mut().zip(paths) {
+            let file = path.clone();
+            rename(
+                &mut renames,
+                &file,
+                String::from("path"),
+                path,
+                renamed.map(|(path, _)| path),
+            );
+        }
+
+        Ok(renames)
+    }
+}
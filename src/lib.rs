@@ -1,12 +1,26 @@
+mod character;
 mod nucc_binary_parsed;
 mod utils;
+mod validation;
+mod xfbin;
 
 use deku::ctx::Endian;
 use regex::Regex;
 use strum::{EnumMessage, IntoEnumIterator};
 use strum_macros::{Display, EnumIter, EnumString};
 
+pub use character::{CharacterEntries, CharacterEntry, CharacterRename};
 pub use nucc_binary_parsed::*;
+pub use utils::{FixedStringOverflow, OverflowPolicy};
+pub use validation::{
+    DanglingReference, Diagnostic, NuccBinaryParsedSet, ReferenceKind, Report, ReportEntry,
+    Severity,
+};
+pub use xfbin::{
+    binary_chunk_prefix, unwrap_binary_chunk, wrap_binary_chunk, Xfbin, XfbinBinaryChunk,
+    XfbinChunk, XfbinChunkMap, XfbinChunkTable, XfbinError, XfbinHeader, XfbinPage, CHUNK_BINARY,
+    CHUNK_NULL, CHUNK_PAGE, XFBIN_MAGIC,
+};
 
 #[derive(Copy, Clone, EnumIter, Display, EnumString)]
 pub enum NuccBinaryType {
@@ -81,6 +95,17 @@ impl NuccBinaryType {
         }
     }
 
+    /// Finds the first type with a pattern matching the path of a chunk.
+    pub fn match_path(path: &str) -> Option<(NuccBinaryType, Endian)> {
+        NuccBinaryType::iter().find_map(|binary_type| {
+            binary_type
+                .patterns()
+                .into_iter()
+                .find(|(regex, _)| regex.is_match(path))
+                .map(|(_, endian)| (binary_type, endian))
+        })
+    }
+
     pub fn examples(&self) -> Vec<String> {
         match self {
             NuccBinaryType::CharaCode(_) => {
@@ -134,6 +159,7 @@ impl NuccBinaryType {
     pub fn version_options(&self) -> Vec<String> {
         match self {
             NuccBinaryType::Ev(_) => EvVersion::iter()
+                .filter(|version| !matches!(version, EvVersion::Custom(_)))
                 .map(|version| {
                     format!(
                         "{} ({})",
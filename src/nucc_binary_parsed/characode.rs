@@ -1,11 +1,16 @@
 use super::endian_from_bool;
 use super::NuccBinaryParsed;
 use super::NuccBinaryType;
-use crate::utils::DekuFixedString;
+use crate::utils::{
+    fit_fixed_string, fixed_string_bytes, DekuFixedString, FixedStringOverflow, OverflowPolicy,
+};
+use crate::validation::{check_string, find_duplicates, Diagnostic};
 
 use deku::ctx::Endian;
 use deku::prelude::*;
+use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Default, Serialize, Deserialize)]
 #[deku_derive(DekuRead, DekuWrite)]
@@ -17,7 +22,7 @@ use serde::{Deserialize, Serialize};
 pub struct Entry {
     pub index: u32,
 
-    #[deku(ctx = "8")]
+    #[deku(ctx = "\"chara\", 8")]
     pub chara: DekuFixedString,
 }
 
@@ -40,6 +45,74 @@ pub struct CharaCode {
     pub big_endian: bool,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CharaCodeError {
+    DuplicateCode(String),
+    UnknownCode(String),
+    TooLong(FixedStringOverflow),
+    IndexOverflow,
+}
+
+impl fmt::Display for CharaCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CharaCodeError::DuplicateCode(code) => {
+                write!(f, "character code {:?} already exists", code)
+            }
+            CharaCodeError::UnknownCode(code) => {
+                write!(f, "character code {:?} does not exist", code)
+            }
+            CharaCodeError::TooLong(overflow) => write!(f, "{}", overflow),
+            CharaCodeError::IndexOverflow => write!(f, "no index is left after {}", u32::MAX),
+        }
+    }
+}
+
+impl std::error::Error for CharaCodeError {}
+
+impl From<FixedStringOverflow> for CharaCodeError {
+    fn from(overflow: FixedStringOverflow) -> Self {
+        CharaCodeError::TooLong(overflow)
+    }
+}
+
+/// Lookup between character codes and indices, for checking references from other files.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CharaCodeMap {
+    by_code: HashMap<String, u32>,
+    by_index: HashMap<u32, String>,
+}
+
+impl CharaCodeMap {
+    /// Adds the entries of another file. Existing codes and indices are kept.
+    pub fn extend(&mut self, chara_code: &CharaCode) {
+        for entry in chara_code.entries.iter() {
+            self.by_code
+                .entry(entry.chara.string.clone())
+                .or_insert(entry.index);
+            self.by_index
+                .entry(entry.index)
+                .or_insert_with(|| entry.chara.string.clone());
+        }
+    }
+
+    pub fn index_of(&self, code: &str) -> Option<u32> {
+        self.by_code.get(code).copied()
+    }
+
+    pub fn code_of(&self, index: u32) -> Option<&str> {
+        self.by_index.get(&index).map(String::as_str)
+    }
+
+    pub fn contains_code(&self, code: &str) -> bool {
+        self.by_code.contains_key(code)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_code.is_empty()
+    }
+}
+
 impl NuccBinaryParsed for CharaCode {
     fn binary_type(&self) -> NuccBinaryType {
         NuccBinaryType::CharaCode(endian_from_bool(self.big_endian))
@@ -59,4 +132,108 @@ impl NuccBinaryParsed for CharaCode {
     {
         serde_json::from_slice(data).unwrap()
     }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            check_string(
+                &mut diagnostics,
+                format!("entries[{}].chara", i),
+                &entry.chara.string,
+                8,
+            );
+        }
+
+        for (i, first) in self.duplicate_indices() {
+            diagnostics.push(Diagnostic::error(
+                format!("entries[{}].index", i),
+                format!("duplicate index, first used by entries[{}]", first),
+            ));
+        }
+
+        for (i, first) in self.duplicate_codes() {
+            diagnostics.push(Diagnostic::error(
+                format!("entries[{}].chara", i),
+                format!("duplicate character code, first used by entries[{}]", first),
+            ));
+        }
+
+        diagnostics
+    }
+}
+
+impl CharaCode {
+    pub fn find_by_code(&self, code: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.chara.string == code)
+    }
+
+    pub fn find_by_index(&self, index: u32) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.index == index)
+    }
+
+    /// One past the highest index in use, or 0 if there are no entries.
+    pub fn next_index(&self) -> Result<u32, CharaCodeError> {
+        match self.entries.iter().map(|entry| entry.index).max() {
+            Some(index) => index.checked_add(1).ok_or(CharaCodeError::IndexOverflow),
+            None => Ok(0),
+        }
+    }
+
+    /// Adds a character with the next free index, returning the index.
+    pub fn add_character(&mut self, code: &str) -> Result<u32, CharaCodeError> {
+        if self.find_by_code(code).is_some() {
+            return Err(CharaCodeError::DuplicateCode(code.to_string()));
+        }
+
+        fixed_string_bytes(&format!("entries[{}].chara", self.entries.len()), code, 8)?;
+
+        let index = self.next_index()?;
+        self.entries.push(Entry {
+            index,
+            chara: DekuFixedString {
+                string: code.to_string(),
+            },
+        });
+        self.count = self.entries.len() as u32;
+
+        Ok(index)
+    }
+
+    /// Pairs of (entry, first entry) that share an index.
+    pub fn duplicate_indices(&self) -> Vec<(usize, usize)> {
+        find_duplicates(self.entries.iter().map(|e| e.index))
+    }
+
+    /// Pairs of (entry, first entry) that share a character code.
+    pub fn duplicate_codes(&self) -> Vec<(usize, usize)> {
+        find_duplicates(self.entries.iter().map(|e| &e.chara.string))
+    }
+
+    pub fn map(&self) -> CharaCodeMap {
+        let mut map = CharaCodeMap::default();
+        map.extend(self);
+        map
+    }
+
+    /// Makes every character code fit in its 8 byte field according to `policy`.
+    /// Returns the codes that were truncated.
+    pub fn fit_strings(
+        &mut self,
+        policy: OverflowPolicy,
+    ) -> Result<Vec<FixedStringOverflow>, FixedStringOverflow> {
+        let mut truncated = Vec::new();
+
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            let field = format!("entries[{}].chara", i);
+            truncated.extend(fit_fixed_string(
+                &field,
+                &mut entry.chara.string,
+                8,
+                policy,
+            )?);
+        }
+
+        Ok(truncated)
+    }
 }
@@ -0,0 +1,905 @@
+//! CPU decoders and encoders for DDS surfaces, converting from and to RGBA8 pixels.
+
+use super::dds_file::DdsFormat;
+
+type Pixel = [u8; 4];
+
+/// Decodes a surface of `width` x `height` pixels into RGBA8, or returns `None` if the format is not supported.
+/// `data` must hold at least one full surface.
+pub(crate) fn decode_surface(
+    format: DdsFormat,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> Option<Vec<u8>> {
+    Some(match format {
+        DdsFormat::Bc1 => decode_blocks(data, width, height, 8, |block| {
+            decode_color_block(block, true)
+        }),
+        DdsFormat::Bc2 => decode_blocks(data, width, height, 16, |block| {
+            let mut pixels = decode_color_block(&block[8..], false);
+            let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                pixel[3] = ((alpha >> (i * 4)) & 0xF) as u8 * 17;
+            }
+            pixels
+        }),
+        DdsFormat::Bc3 => decode_blocks(data, width, height, 16, |block| {
+            let mut pixels = decode_color_block(&block[8..], false);
+            for (pixel, alpha) in pixels
+                .iter_mut()
+                .zip(decode_channel_block(&block[..8], false))
+            {
+                pixel[3] = alpha;
+            }
+            pixels
+        }),
+        DdsFormat::Bc4 | DdsFormat::Bc4S => decode_blocks(data, width, height, 8, |block| {
+            decode_channel_block(block, format == DdsFormat::Bc4S).map(|r| [r, r, r, 0xFF])
+        }),
+        DdsFormat::Bc5 | DdsFormat::Bc5S => decode_blocks(data, width, height, 16, |block| {
+            let signed = format == DdsFormat::Bc5S;
+            let (red, green) = (
+                decode_channel_block(&block[..8], signed),
+                decode_channel_block(&block[8..], signed),
+            );
+            std::array::from_fn(|i| [red[i], green[i], 0, 0xFF])
+        }),
+        DdsFormat::Bc7 => decode_blocks(data, width, height, 16, decode_bc7_block),
+        _ => {
+            let bytes = format.bits_per_pixel()? / 8;
+            data.chunks_exact(bytes)
+                .take(width * height)
+                .flat_map(|pixel| decode_pixel(format, pixel))
+                .collect()
+        }
+    })
+}
+
+/// Encodes RGBA8 pixels into a surface, or returns `None` if the format is not supported.
+/// BC7 surfaces are encoded with mode 6 only, which favors speed over quality.
+pub(crate) fn encode_surface(
+    format: DdsFormat,
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+) -> Option<Vec<u8>> {
+    Some(match format {
+        DdsFormat::Bc1 => encode_blocks(rgba, width, height, |pixels| {
+            encode_color_block(pixels, true).to_vec()
+        }),
+        DdsFormat::Bc2 => encode_blocks(rgba, width, height, |pixels| {
+            let alpha = pixels.iter().enumerate().fold(0u64, |alpha, (i, pixel)| {
+                alpha | (((pixel[3] as u64 * 15 + 127) / 255) << (i * 4))
+            });
+
+            [alpha.to_le_bytes(), encode_color_block(pixels, false)].concat()
+        }),
+        DdsFormat::Bc3 => encode_blocks(rgba, width, height, |pixels| {
+            [
+                encode_channel_block(&pixels.map(|pixel| pixel[3]), false),
+                encode_color_block(pixels, false),
+            ]
+            .concat()
+        }),
+        DdsFormat::Bc4 | DdsFormat::Bc4S => encode_blocks(rgba, width, height, |pixels| {
+            encode_channel_block(&pixels.map(|pixel| pixel[0]), format == DdsFormat::Bc4S).to_vec()
+        }),
+        DdsFormat::Bc5 | DdsFormat::Bc5S => encode_blocks(rgba, width, height, |pixels| {
+            let signed = format == DdsFormat::Bc5S;
+            [
+                encode_channel_block(&pixels.map(|pixel| pixel[0]), signed),
+                encode_channel_block(&pixels.map(|pixel| pixel[1]), signed),
+            ]
+            .concat()
+        }),
+        DdsFormat::Bc7 => encode_blocks(rgba, width, height, |pixels| {
+            encode_bc7_block(pixels).to_vec()
+        }),
+        _ => {
+            format.bits_per_pixel()?;
+            rgba.chunks_exact(4)
+                .take(width * height)
+                .flat_map(|pixel| encode_pixel(format, pixel.try_into().unwrap()))
+                .collect()
+        }
+    })
+}
+
+/// Halves each dimension of a stack of `depth` RGBA8 slices with a box filter.
+pub(crate) fn downsample(
+    rgba: &[u8],
+    (width, height, depth): (usize, usize, usize),
+) -> (Vec<u8>, (usize, usize, usize)) {
+    let (new_width, new_height, new_depth) =
+        ((width / 2).max(1), (height / 2).max(1), (depth / 2).max(1));
+
+    let mut output = Vec::with_capacity(new_width * new_height * new_depth * 4);
+    for z in 0..new_depth {
+        for y in 0..new_height {
+            for x in 0..new_width {
+                let mut sum = [0u32; 4];
+                for (dx, dy, dz) in (0..8).map(|i| (i & 1, (i >> 1) & 1, i >> 2)) {
+                    let (sx, sy, sz) = (
+                        (x * 2 + dx).min(width - 1),
+                        (y * 2 + dy).min(height - 1),
+                        (z * 2 + dz).min(depth - 1),
+                    );
+
+                    let offset = ((sz * height + sy) * width + sx) * 4;
+                    for (c, sum) in sum.iter_mut().enumerate() {
+                        *sum += rgba[offset + c] as u32;
+                    }
+                }
+
+                output.extend(sum.map(|sum| ((sum + 4) / 8) as u8));
+            }
+        }
+    }
+
+    (output, (new_width, new_height, new_depth))
+}
+
+fn decode_blocks(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    block_size: usize,
+    decode: impl Fn(&[u8]) -> [Pixel; 16],
+) -> Vec<u8> {
+    let mut rgba = vec![0; width * height * 4];
+    let blocks_x = width.div_ceil(4);
+
+    for (i, block) in data
+        .chunks_exact(block_size)
+        .take(blocks_x * height.div_ceil(4))
+        .enumerate()
+    {
+        let (block_x, block_y) = ((i % blocks_x) * 4, (i / blocks_x) * 4);
+
+        for (j, pixel) in decode(block).iter().enumerate() {
+            let (x, y) = (block_x + j % 4, block_y + j / 4);
+            if x < width && y < height {
+                rgba[(y * width + x) * 4..][..4].copy_from_slice(pixel);
+            }
+        }
+    }
+
+    rgba
+}
+
+fn encode_blocks(
+    rgba: &[u8],
+    width: usize,
+    height: usize,
+    encode: impl Fn(&[Pixel; 16]) -> Vec<u8>,
+) -> Vec<u8> {
+    let mut output = Vec::new();
+
+    for block_y in (0..height).step_by(4) {
+        for block_x in (0..width).step_by(4) {
+            // Pixels outside of the surface repeat the edge, so they do not skew the endpoints
+            let pixels = std::array::from_fn(|j| {
+                let x = (block_x + j % 4).min(width - 1);
+                let y = (block_y + j / 4).min(height - 1);
+                rgba[(y * width + x) * 4..][..4].try_into().unwrap()
+            });
+
+            output.extend(encode(&pixels));
+        }
+    }
+
+    output
+}
+
+fn decode_pixel(format: DdsFormat, bytes: &[u8]) -> Pixel {
+    let expand = |value: u16, bits: u32| {
+        ((value as u32 * 255 + (1 << bits) / 2 - 1) / ((1 << bits) - 1)) as u8
+    };
+
+    let word = || u16::from_le_bytes([bytes[0], bytes[1]]);
+    match format {
+        DdsFormat::Rgba8 => [bytes[0], bytes[1], bytes[2], bytes[3]],
+        DdsFormat::Bgra8 => [bytes[2], bytes[1], bytes[0], bytes[3]],
+        DdsFormat::Bgrx8 | DdsFormat::Bgr8 => [bytes[2], bytes[1], bytes[0], 0xFF],
+        DdsFormat::B5G6R5 => {
+            let value = word();
+            [
+                expand(value >> 11, 5),
+                expand((value >> 5) & 0x3F, 6),
+                expand(value & 0x1F, 5),
+                0xFF,
+            ]
+        }
+        DdsFormat::B5G5R5A1 => {
+            let value = word();
+            [
+                expand((value >> 10) & 0x1F, 5),
+                expand((value >> 5) & 0x1F, 5),
+                expand(value & 0x1F, 5),
+                expand(value >> 15, 1),
+            ]
+        }
+        DdsFormat::B4G4R4A4 => {
+            let value = word();
+            [
+                expand((value >> 8) & 0xF, 4),
+                expand((value >> 4) & 0xF, 4),
+                expand(value & 0xF, 4),
+                expand(value >> 12, 4),
+            ]
+        }
+        DdsFormat::L8 => [bytes[0], bytes[0], bytes[0], 0xFF],
+        DdsFormat::A8 => [0xFF, 0xFF, 0xFF, bytes[0]],
+        _ => unreachable!(),
+    }
+}
+
+fn encode_pixel(format: DdsFormat, [r, g, b, a]: Pixel) -> Vec<u8> {
+    let reduce = |value: u8, bits: u32| ((value as u32 * ((1 << bits) - 1) + 127) / 255) as u16;
+
+    match format {
+        DdsFormat::Rgba8 => vec![r, g, b, a],
+        DdsFormat::Bgra8 => vec![b, g, r, a],
+        DdsFormat::Bgrx8 => vec![b, g, r, 0xFF],
+        DdsFormat::Bgr8 => vec![b, g, r],
+        DdsFormat::B5G6R5 => (reduce(r, 5) << 11 | reduce(g, 6) << 5 | reduce(b, 5))
+            .to_le_bytes()
+            .to_vec(),
+        DdsFormat::B5G5R5A1 => {
+            (reduce(a, 1) << 15 | reduce(r, 5) << 10 | reduce(g, 5) << 5 | reduce(b, 5))
+                .to_le_bytes()
+                .to_vec()
+        }
+        DdsFormat::B4G4R4A4 => {
+            (reduce(a, 4) << 12 | reduce(r, 4) << 8 | reduce(g, 4) << 4 | reduce(b, 4))
+                .to_le_bytes()
+                .to_vec()
+        }
+        DdsFormat::L8 => vec![((r as u32 * 77 + g as u32 * 150 + b as u32 * 29 + 128) >> 8) as u8],
+        DdsFormat::A8 => vec![a],
+        _ => unreachable!(),
+    }
+}
+
+fn rgb565(color: u16) -> Pixel {
+    let (r, g, b) = ((color >> 11) & 0x1F, (color >> 5) & 0x3F, color & 0x1F);
+    [
+        (r << 3 | r >> 2) as u8,
+        (g << 2 | g >> 4) as u8,
+        (b << 3 | b >> 2) as u8,
+        0xFF,
+    ]
+}
+
+fn to_rgb565(pixel: &Pixel) -> u16 {
+    let reduce = |value: u8, max: u32| ((value as u32 * max + 127) / 255) as u16;
+    reduce(pixel[0], 31) << 11 | reduce(pixel[1], 63) << 5 | reduce(pixel[2], 31)
+}
+
+/// Palette of a BC1 color block. BC2 and BC3 blocks always use the four color mode.
+fn color_palette(color0: u16, color1: u16, allow_alpha: bool) -> [Pixel; 4] {
+    let (start, end) = (rgb565(color0), rgb565(color1));
+    let mix = |start_weight: u32, end_weight: u32| -> Pixel {
+        let total = start_weight + end_weight;
+        std::array::from_fn(|c| {
+            ((start[c] as u32 * start_weight + end[c] as u32 * end_weight) / total) as u8
+        })
+    };
+
+    if color0 > color1 || !allow_alpha {
+        [start, end, mix(2, 1), mix(1, 2)]
+    } else {
+        [start, end, mix(1, 1), [0; 4]]
+    }
+}
+
+fn decode_color_block(block: &[u8], allow_alpha: bool) -> [Pixel; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+
+    let palette = color_palette(color0, color1, allow_alpha);
+    std::array::from_fn(|i| palette[((indices >> (i * 2)) & 3) as usize])
+}
+
+fn encode_color_block(pixels: &[Pixel; 16], allow_alpha: bool) -> [u8; 8] {
+    let transparent = |pixel: &Pixel| allow_alpha && pixel[3] < 0x80;
+
+    let opaque: Vec<Pixel> = pixels.iter().filter(|p| !transparent(p)).copied().collect();
+    if opaque.is_empty() {
+        return [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+    }
+
+    let (min, max) = endpoints(&opaque, 3);
+    let (mut color0, mut color1) = (to_rgb565(&max), to_rgb565(&min));
+
+    // The order of the endpoints selects between the four color and the three color + transparent modes
+    let has_transparent = opaque.len() != pixels.len();
+    if (color0 < color1 && !has_transparent) || (color0 > color1 && has_transparent) {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+
+    let palette = color_palette(color0, color1, allow_alpha);
+    let colors = if color0 > color1 || !allow_alpha {
+        4
+    } else {
+        3
+    };
+
+    let indices = pixels.iter().enumerate().fold(0u32, |indices, (i, pixel)| {
+        let index = if transparent(pixel) {
+            3
+        } else {
+            nearest(&palette[..colors], pixel, 3)
+        };
+
+        indices | (index as u32) << (i * 2)
+    });
+
+    let mut block = [0; 8];
+    block[..2].copy_from_slice(&color0.to_le_bytes());
+    block[2..4].copy_from_slice(&color1.to_le_bytes());
+    block[4..].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+/// Palette of a single channel block, as used by BC3 alpha, BC4 and BC5.
+/// Range of the values of a channel block: 0 to 255, or -127 to 127 for signed blocks.
+fn channel_range(signed: bool) -> (i32, i32) {
+    if signed {
+        (-127, 127)
+    } else {
+        (0, 0xFF)
+    }
+}
+
+/// Reads an endpoint of a channel block, where -128 is the same as -127 in signed blocks.
+fn channel_endpoint(byte: u8, signed: bool) -> i32 {
+    if signed {
+        (byte as i8).max(-127) as i32
+    } else {
+        byte as i32
+    }
+}
+
+/// Maps a channel value to a byte, so that signed -1.0 to 1.0 covers 0 to 255.
+fn channel_to_byte(value: i32, signed: bool) -> u8 {
+    if signed {
+        (((value + 127) * 0xFF + 127) / 254) as u8
+    } else {
+        value as u8
+    }
+}
+
+fn channel_from_byte(byte: u8, signed: bool) -> i32 {
+    if signed {
+        (byte as i32 * 254 + 127) / 0xFF - 127
+    } else {
+        byte as i32
+    }
+}
+
+fn channel_palette(value0: i32, value1: i32, signed: bool) -> [i32; 8] {
+    let (low, high) = channel_range(signed);
+    let mut palette = [value0, value1, 0, 0, 0, 0, low, high];
+
+    if value0 > value1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as i32) * value0 + i as i32 * value1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as i32) * value0 + i as i32 * value1) / 5;
+        }
+    }
+
+    palette
+}
+
+fn decode_channel_block(block: &[u8], signed: bool) -> [u8; 16] {
+    let palette = channel_palette(
+        channel_endpoint(block[0], signed),
+        channel_endpoint(block[1], signed),
+        signed,
+    );
+
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+
+    std::array::from_fn(|i| channel_to_byte(palette[((indices >> (i * 3)) & 7) as usize], signed))
+}
+
+fn encode_channel_block(values: &[u8; 16], signed: bool) -> [u8; 8] {
+    let values = values.map(|value| channel_from_byte(value, signed));
+    let (min, max) = (*values.iter().min().unwrap(), *values.iter().max().unwrap());
+    let palette = channel_palette(max, min, signed);
+
+    let indices = values.iter().enumerate().fold(0u64, |indices, (i, value)| {
+        let index = (0..8).min_by_key(|&j| (palette[j] - *value).abs()).unwrap();
+
+        indices | (index as u64) << (i * 3)
+    });
+
+    // Signed endpoints are stored as `i8`
+    let mut block = [0; 8];
+    block[0] = max as u8;
+    block[1] = min as u8;
+    block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    block
+}
+
+/// Picks the endpoints of a block from the bounding box of its pixels,
+/// flipping channels that are inversely correlated with the channel with the largest range.
+fn endpoints(pixels: &[Pixel], channels: usize) -> (Pixel, Pixel) {
+    let (mut min, mut max) = ([0; 4], [0; 4]);
+    for c in 0..channels {
+        min[c] = pixels.iter().map(|pixel| pixel[c]).min().unwrap();
+        max[c] = pixels.iter().map(|pixel| pixel[c]).max().unwrap();
+    }
+
+    let axis = (0..channels).max_by_key(|&c| max[c] - min[c]).unwrap();
+    let mean =
+        |c: usize| pixels.iter().map(|pixel| pixel[c] as i32).sum::<i32>() / pixels.len() as i32;
+    let axis_mean = mean(axis);
+
+    for c in 0..channels {
+        let channel_mean = mean(c);
+        let covariance: i32 = pixels
+            .iter()
+            .map(|pixel| (pixel[c] as i32 - channel_mean) * (pixel[axis] as i32 - axis_mean))
+            .sum();
+
+        if covariance < 0 {
+            std::mem::swap(&mut min[c], &mut max[c]);
+        }
+    }
+
+    (min, max)
+}
+
+fn nearest(palette: &[Pixel], pixel: &Pixel, channels: usize) -> usize {
+    (0..palette.len())
+        .min_by_key(|&i| {
+            (0..channels)
+                .map(|c| (palette[i][c] as i32 - pixel[c] as i32).pow(2))
+                .sum::<i32>()
+        })
+        .unwrap()
+}
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    index_selection_bits: usize,
+    color_bits: usize,
+    alpha_bits: usize,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: usize,
+    secondary_index_bits: usize,
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: true,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 3,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 2,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 4,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+];
+
+/// Subset of each pixel in the 2 subset partitions, one bit per pixel
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+const BC7_PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor pixel of the second subset in the 2 subset partitions
+const BC7_ANCHORS_2: [usize; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixels of the second and third subsets in the 3 subset partitions
+const BC7_ANCHORS_3: [[usize; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6,
+        8, 5, 15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8,
+        5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3,
+        15, 6, 10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15,
+        15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, count: usize) -> u32 {
+        let mut value = 0;
+        for i in 0..count {
+            let bit = (self.data[self.position / 8] >> (self.position % 8)) & 1;
+            value |= (bit as u32) << i;
+            self.position += 1;
+        }
+
+        value
+    }
+}
+
+#[derive(Default)]
+struct BitWriter {
+    data: [u8; 16],
+    position: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: usize) {
+        for i in 0..count {
+            self.data[self.position / 8] |= (((value >> i) & 1) as u8) << (self.position % 8);
+            self.position += 1;
+        }
+    }
+}
+
+fn bc7_weight(bits: usize, index: u32) -> u32 {
+    match bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        _ => BC7_WEIGHTS_4[index as usize],
+    }
+}
+
+fn bc7_interpolate(start: u32, end: u32, weight: u32) -> u8 {
+    (((64 - weight) * start + weight * end + 32) >> 6) as u8
+}
+
+fn decode_bc7_block(block: &[u8]) -> [Pixel; 16] {
+    let mode_index = block[0].trailing_zeros() as usize;
+    if mode_index >= BC7_MODES.len() {
+        return [[0; 4]; 16];
+    }
+
+    let mode = &BC7_MODES[mode_index];
+    let mut bits = BitReader {
+        data: block,
+        position: mode_index + 1,
+    };
+
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let mut endpoints = [[[0u32; 4]; 2]; 3];
+    for c in 0..3 {
+        for subset in endpoints.iter_mut().take(mode.subsets) {
+            for endpoint in subset.iter_mut() {
+                endpoint[c] = bits.read(mode.color_bits);
+            }
+        }
+    }
+
+    if mode.alpha_bits != 0 {
+        for subset in endpoints.iter_mut().take(mode.subsets) {
+            for endpoint in subset.iter_mut() {
+                endpoint[3] = bits.read(mode.alpha_bits);
+            }
+        }
+    }
+
+    let channels = if mode.alpha_bits != 0 { 4 } else { 3 };
+    let has_pbits = mode.endpoint_pbits || mode.shared_pbits;
+
+    if has_pbits {
+        for subset in endpoints.iter_mut().take(mode.subsets) {
+            let shared = mode.shared_pbits.then(|| bits.read(1));
+            for endpoint in subset.iter_mut() {
+                let pbit = shared.unwrap_or_else(|| bits.read(1));
+                for value in endpoint.iter_mut().take(channels) {
+                    *value = *value << 1 | pbit;
+                }
+            }
+        }
+    }
+
+    let expand = |value: u32, precision: usize| {
+        let value = value << (8 - precision);
+        value | value >> precision
+    };
+
+    let pbit = has_pbits as usize;
+    for subset in endpoints.iter_mut().take(mode.subsets) {
+        for endpoint in subset.iter_mut() {
+            for value in endpoint.iter_mut().take(3) {
+                *value = expand(*value, mode.color_bits + pbit);
+            }
+
+            endpoint[3] = if mode.alpha_bits != 0 {
+                expand(endpoint[3], mode.alpha_bits + pbit)
+            } else {
+                0xFF
+            };
+        }
+    }
+
+    let subset_of = |i: usize| match mode.subsets {
+        2 => ((BC7_PARTITIONS_2[partition] >> i) & 1) as usize,
+        3 => BC7_PARTITIONS_3[partition][i] as usize,
+        _ => 0,
+    };
+
+    let is_anchor = |i: usize| {
+        i == 0
+            || match mode.subsets {
+                2 => i == BC7_ANCHORS_2[partition],
+                3 => i == BC7_ANCHORS_3[0][partition] || i == BC7_ANCHORS_3[1][partition],
+                _ => false,
+            }
+    };
+
+    let indices: [u32; 16] =
+        std::array::from_fn(|i| bits.read(mode.index_bits - is_anchor(i) as usize));
+    let secondary_indices: [u32; 16] = std::array::from_fn(|i| match mode.secondary_index_bits {
+        0 => 0,
+        count => bits.read(count - (i == 0) as usize),
+    });
+
+    std::array::from_fn(|i| {
+        let [start, end] = endpoints[subset_of(i)];
+
+        let ((color_index, color_bits), (alpha_index, alpha_bits)) =
+            match (mode.secondary_index_bits, index_selection) {
+                (0, _) => ((indices[i], mode.index_bits), (indices[i], mode.index_bits)),
+                (_, 0) => (
+                    (indices[i], mode.index_bits),
+                    (secondary_indices[i], mode.secondary_index_bits),
+                ),
+                _ => (
+                    (secondary_indices[i], mode.secondary_index_bits),
+                    (indices[i], mode.index_bits),
+                ),
+            };
+
+        let color_weight = bc7_weight(color_bits, color_index);
+        let alpha_weight = bc7_weight(alpha_bits, alpha_index);
+
+        let mut pixel: Pixel = std::array::from_fn(|c| match c {
+            3 => bc7_interpolate(start[3], end[3], alpha_weight),
+            c => bc7_interpolate(start[c], end[c], color_weight),
+        });
+
+        if rotation != 0 {
+            pixel.swap(3, rotation as usize - 1);
+        }
+
+        pixel
+    })
+}
+
+/// Quantizes an endpoint to 7 bits per channel plus a shared p-bit, as used by BC7 mode 6.
+fn quantize_bc7_endpoint(endpoint: &Pixel) -> ([u32; 4], u32) {
+    (0..2)
+        .map(|pbit| {
+            let quantized =
+                endpoint.map(|value| ((value as i32 - pbit + 1) / 2).clamp(0, 127) as u32);
+            let error: i32 = (0..4)
+                .map(|c| ((quantized[c] << 1 | pbit as u32) as i32 - endpoint[c] as i32).pow(2))
+                .sum();
+
+            (error, quantized, pbit as u32)
+        })
+        .min_by_key(|(error, _, _)| *error)
+        .map(|(_, quantized, pbit)| (quantized, pbit))
+        .unwrap()
+}
+
+fn encode_bc7_block(pixels: &[Pixel; 16]) -> [u8; 16] {
+    let (min, max) = endpoints(pixels, 4);
+    let mut quantized = [quantize_bc7_endpoint(&min), quantize_bc7_endpoint(&max)];
+
+    let [start, end] = quantized.map(|(values, pbit)| values.map(|value| value << 1 | pbit));
+    let palette: [Pixel; 16] = std::array::from_fn(|i| {
+        std::array::from_fn(|c| bc7_interpolate(start[c], end[c], BC7_WEIGHTS_4[i]))
+    });
+
+    let mut indices = pixels.map(|pixel| nearest(&palette, &pixel, 4) as u32);
+
+    // The most significant bit of the first index is implied to be 0
+    if indices[0] >= 8 {
+        quantized.swap(0, 1);
+        indices = indices.map(|index| 15 - index);
+    }
+
+    let mut bits = BitWriter::default();
+    bits.write(1 << 6, 7);
+
+    for c in 0..4 {
+        for (values, _) in quantized.iter() {
+            bits.write(values[c], 7);
+        }
+    }
+
+    for (_, pbit) in quantized.iter() {
+        bits.write(*pbit, 1);
+    }
+
+    for (i, index) in indices.iter().enumerate() {
+        bits.write(*index, if i == 0 { 3 } else { 4 });
+    }
+
+    bits.data
+}
@@ -1,11 +1,742 @@
+use super::dds_codec::{decode_surface, downsample, encode_surface};
+use super::png_file::PNG_SIGNATURE;
 use super::NuccBinaryParsed;
 use super::NuccBinaryType;
+use crate::validation::Diagnostic;
 
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+
+const DDS_MAGIC: &[u8] = b"DDS ";
+const HEADER_SIZE: usize = 0x80;
+const DX10_HEADER_SIZE: usize = 0x14;
+
+const PNG_SOURCE_KEYWORD: &str = "nucc_dds";
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_DEPTH: u32 = 0x800000;
+
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xFC00;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+
+const DX10_RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+const DX10_MISC_TEXTURECUBE: u32 = 0x4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DdsFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc4S,
+    Bc5,
+    Bc5S,
+    Bc6h,
+    Bc7,
+    Rgba8,
+    Bgra8,
+    Bgrx8,
+    Bgr8,
+    B5G6R5,
+    B5G5R5A1,
+    B4G4R4A4,
+    L8,
+    A8,
+    Unknown,
+}
+
+impl DdsFormat {
+    /// Size in bytes of a 4x4 block, for block compressed formats.
+    pub fn block_size(&self) -> Option<usize> {
+        match self {
+            DdsFormat::Bc1 | DdsFormat::Bc4 | DdsFormat::Bc4S => Some(8),
+            DdsFormat::Bc2
+            | DdsFormat::Bc3
+            | DdsFormat::Bc5
+            | DdsFormat::Bc5S
+            | DdsFormat::Bc6h
+            | DdsFormat::Bc7 => Some(16),
+            _ => None,
+        }
+    }
+
+    /// Size in bits of a pixel, for uncompressed formats.
+    pub fn bits_per_pixel(&self) -> Option<usize> {
+        match self {
+            DdsFormat::Rgba8 | DdsFormat::Bgra8 | DdsFormat::Bgrx8 => Some(32),
+            DdsFormat::Bgr8 => Some(24),
+            DdsFormat::B5G6R5 | DdsFormat::B5G5R5A1 | DdsFormat::B4G4R4A4 => Some(16),
+            DdsFormat::L8 | DdsFormat::A8 => Some(8),
+            _ => None,
+        }
+    }
+
+    /// Size in bytes of a single surface of the given dimensions.
+    pub fn surface_size(&self, width: u32, height: u32) -> Option<usize> {
+        let (width, height) = (width.max(1) as usize, height.max(1) as usize);
+
+        match (self.block_size(), self.bits_per_pixel()) {
+            (Some(block_size), _) => width
+                .div_ceil(4)
+                .checked_mul(height.div_ceil(4))?
+                .checked_mul(block_size),
+            (_, Some(bits)) => width.checked_mul(bits)?.div_ceil(8).checked_mul(height),
+            _ => None,
+        }
+    }
+
+    /// Whether surfaces of this format can be converted from and to RGBA.
+    pub fn is_convertible(&self) -> bool {
+        !matches!(self, DdsFormat::Bc6h | DdsFormat::Unknown)
+    }
+
+    fn from_dxgi(dxgi_format: u32) -> Self {
+        match dxgi_format {
+            70..=72 => DdsFormat::Bc1,
+            73..=75 => DdsFormat::Bc2,
+            76..=78 => DdsFormat::Bc3,
+            79 | 80 => DdsFormat::Bc4,
+            81 => DdsFormat::Bc4S,
+            82 | 83 => DdsFormat::Bc5,
+            84 => DdsFormat::Bc5S,
+            94..=96 => DdsFormat::Bc6h,
+            97..=99 => DdsFormat::Bc7,
+            27..=29 => DdsFormat::Rgba8,
+            87 | 90 | 91 => DdsFormat::Bgra8,
+            88 | 92 | 93 => DdsFormat::Bgrx8,
+            85 => DdsFormat::B5G6R5,
+            86 => DdsFormat::B5G5R5A1,
+            115 => DdsFormat::B4G4R4A4,
+            61 => DdsFormat::L8,
+            65 => DdsFormat::A8,
+            _ => DdsFormat::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DdsPixelFormat {
+    pub flags: u32,
+    pub four_cc: [u8; 4],
+    pub rgb_bit_count: u32,
+    pub r_mask: u32,
+    pub g_mask: u32,
+    pub b_mask: u32,
+    pub a_mask: u32,
+}
+
+impl DdsPixelFormat {
+    pub fn four_cc_string(&self) -> Option<String> {
+        (self.flags & DDPF_FOURCC != 0).then(|| String::from_utf8_lossy(&self.four_cc).to_string())
+    }
+
+    fn format(&self) -> DdsFormat {
+        if self.flags & DDPF_FOURCC != 0 {
+            return match &self.four_cc {
+                b"DXT1" => DdsFormat::Bc1,
+                b"DXT2" | b"DXT3" => DdsFormat::Bc2,
+                b"DXT4" | b"DXT5" => DdsFormat::Bc3,
+                b"ATI1" | b"BC4U" => DdsFormat::Bc4,
+                b"BC4S" => DdsFormat::Bc4S,
+                b"ATI2" | b"BC5U" => DdsFormat::Bc5,
+                b"BC5S" => DdsFormat::Bc5S,
+                _ => DdsFormat::Unknown,
+            };
+        }
+
+        if self.flags & DDPF_RGB == 0 {
+            return match self.rgb_bit_count {
+                8 if self.flags & DDPF_LUMINANCE != 0 => DdsFormat::L8,
+                8 if self.flags & DDPF_ALPHA != 0 => DdsFormat::A8,
+                _ => DdsFormat::Unknown,
+            };
+        }
+
+        let has_alpha = self.flags & DDPF_ALPHAPIXELS != 0;
+        let masks = (self.r_mask, self.g_mask, self.b_mask);
+
+        match (self.rgb_bit_count, masks) {
+            (32, (0xFF0000, 0xFF00, 0xFF)) if has_alpha => DdsFormat::Bgra8,
+            (32, (0xFF0000, 0xFF00, 0xFF)) => DdsFormat::Bgrx8,
+            (32, (0xFF, 0xFF00, 0xFF0000)) => DdsFormat::Rgba8,
+            (24, (0xFF0000, 0xFF00, 0xFF)) => DdsFormat::Bgr8,
+            (16, (0xF800, 0x7E0, 0x1F)) => DdsFormat::B5G6R5,
+            (16, (0x7C00, 0x3E0, 0x1F)) if has_alpha => DdsFormat::B5G5R5A1,
+            (16, (0xF00, 0xF0, 0xF)) if has_alpha => DdsFormat::B4G4R4A4,
+            _ => DdsFormat::Unknown,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DdsDx10Header {
+    pub dxgi_format: u32,
+    pub resource_dimension: u32,
+    pub misc_flag: u32,
+    pub array_size: u32,
+    pub misc_flags2: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DdsHeader {
+    pub flags: u32,
+    pub height: u32,
+    pub width: u32,
+    pub pitch_or_linear_size: u32,
+    pub depth: u32,
+    pub mip_count: u32,
+    pub pixel_format: DdsPixelFormat,
+    pub caps: u32,
+    pub caps2: u32,
+    pub dx10: Option<DdsDx10Header>,
+}
+
+impl DdsHeader {
+    pub fn parse(data: &[u8]) -> Result<Self, DdsError> {
+        let read_u32 = |offset: usize| {
+            data.get(offset..offset + 4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                .ok_or(DdsError::TooShort(data.len()))
+        };
+
+        if data.len() < HEADER_SIZE {
+            return Err(DdsError::TooShort(data.len()));
+        }
+
+        if &data[..4] != DDS_MAGIC {
+            return Err(DdsError::InvalidMagic);
+        }
+
+        let header_size = read_u32(0x04)?;
+        if header_size != 124 {
+            return Err(DdsError::InvalidHeaderSize(header_size));
+        }
+
+        let pixel_format = DdsPixelFormat {
+            flags: read_u32(0x50)?,
+            four_cc: data[0x54..0x58].try_into().unwrap(),
+            rgb_bit_count: read_u32(0x58)?,
+            r_mask: read_u32(0x5C)?,
+            g_mask: read_u32(0x60)?,
+            b_mask: read_u32(0x64)?,
+            a_mask: read_u32(0x68)?,
+        };
+
+        let dx10 = if pixel_format.flags & DDPF_FOURCC != 0 && &pixel_format.four_cc == b"DX10" {
+            Some(DdsDx10Header {
+                dxgi_format: read_u32(0x80)?,
+                resource_dimension: read_u32(0x84)?,
+                misc_flag: read_u32(0x88)?,
+                array_size: read_u32(0x8C)?,
+                misc_flags2: read_u32(0x90)?,
+            })
+        } else {
+            None
+        };
+
+        let header = Self {
+            flags: read_u32(0x08)?,
+            height: read_u32(0x0C)?,
+            width: read_u32(0x10)?,
+            pitch_or_linear_size: read_u32(0x14)?,
+            depth: read_u32(0x18)?,
+            mip_count: read_u32(0x1C)?,
+            pixel_format,
+            caps: read_u32(0x6C)?,
+            caps2: read_u32(0x70)?,
+            dx10,
+        };
+
+        if header.surface_count() == 0 {
+            return Err(DdsError::NoSurfaces);
+        }
+
+        Ok(header)
+    }
+
+    /// Header of an uncompressed BGRA8 texture without mips.
+    pub fn bgra8(width: u32, height: u32) -> Self {
+        Self {
+            flags: DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PITCH | DDSD_PIXELFORMAT,
+            height,
+            width,
+            pitch_or_linear_size: width * 4,
+            depth: 0,
+            mip_count: 0,
+            pixel_format: DdsPixelFormat {
+                flags: DDPF_RGB | DDPF_ALPHAPIXELS,
+                four_cc: [0; 4],
+                rgb_bit_count: 32,
+                r_mask: 0xFF0000,
+                g_mask: 0xFF00,
+                b_mask: 0xFF,
+                a_mask: 0xFF000000,
+            },
+            caps: DDSCAPS_TEXTURE,
+            caps2: 0,
+            dx10: None,
+        }
+    }
+
+    /// Writes the header fields over `bytes`, keeping the reserved fields already there.
+    pub fn write_to(&self, bytes: &mut Vec<u8>) {
+        if bytes.len() < self.data_offset() {
+            bytes.resize(self.data_offset(), 0);
+        }
+
+        let mut write_u32 = |offset: usize, value: u32| {
+            bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        };
+
+        write_u32(0x04, 124);
+        write_u32(0x08, self.flags);
+        write_u32(0x0C, self.height);
+        write_u32(0x10, self.width);
+        write_u32(0x14, self.pitch_or_linear_size);
+        write_u32(0x18, self.depth);
+        write_u32(0x1C, self.mip_count);
+        write_u32(0x4C, 32);
+        write_u32(0x50, self.pixel_format.flags);
+        write_u32(0x58, self.pixel_format.rgb_bit_count);
+        write_u32(0x5C, self.pixel_format.r_mask);
+        write_u32(0x60, self.pixel_format.g_mask);
+        write_u32(0x64, self.pixel_format.b_mask);
+        write_u32(0x68, self.pixel_format.a_mask);
+        write_u32(0x6C, self.caps);
+        write_u32(0x70, self.caps2);
+
+        if let Some(dx10) = &self.dx10 {
+            write_u32(0x80, dx10.dxgi_format);
+            write_u32(0x84, dx10.resource_dimension);
+            write_u32(0x88, dx10.misc_flag);
+            write_u32(0x8C, dx10.array_size);
+            write_u32(0x90, dx10.misc_flags2);
+        }
+
+        bytes[..4].copy_from_slice(DDS_MAGIC);
+        bytes[0x54..0x58].copy_from_slice(&self.pixel_format.four_cc);
+    }
+
+    /// Offset of the surfaces from the start of the file.
+    pub fn data_offset(&self) -> usize {
+        HEADER_SIZE + self.dx10.as_ref().map_or(0, |_| DX10_HEADER_SIZE)
+    }
+
+    pub fn format(&self) -> DdsFormat {
+        match &self.dx10 {
+            Some(dx10) => DdsFormat::from_dxgi(dx10.dxgi_format),
+            None => self.pixel_format.format(),
+        }
+    }
+
+    /// Number of mips, limited to the size of a full chain down to 1x1.
+    pub fn mip_count(&self) -> u32 {
+        if self.flags & DDSD_MIPMAPCOUNT != 0 {
+            self.mip_count.clamp(1, self.max_mip_count())
+        } else {
+            1
+        }
+    }
+
+    /// Number of mips in a full chain down to 1x1.
+    pub fn max_mip_count(&self) -> u32 {
+        let largest = self.width.max(self.height).max(self.depth());
+        (32 - largest.leading_zeros()).max(1)
+    }
+
+    pub fn is_volume(&self) -> bool {
+        match &self.dx10 {
+            Some(dx10) => dx10.resource_dimension == DX10_RESOURCE_DIMENSION_TEXTURE3D,
+            None => self.caps2 & DDSCAPS2_VOLUME != 0 && self.flags & DDSD_DEPTH != 0,
+        }
+    }
+
+    pub fn is_cubemap(&self) -> bool {
+        match &self.dx10 {
+            Some(dx10) => dx10.misc_flag & DX10_MISC_TEXTURECUBE != 0,
+            None => self.caps2 & DDSCAPS2_CUBEMAP != 0,
+        }
+    }
+
+    pub fn depth(&self) -> u32 {
+        if self.is_volume() {
+            self.depth.max(1)
+        } else {
+            1
+        }
+    }
+
+    /// Number of array layers, which is always 1 for non-DX10 files.
+    pub fn array_size(&self) -> u32 {
+        self.dx10.as_ref().map_or(1, |dx10| dx10.array_size.max(1))
+    }
+
+    /// Number of 2D surfaces (each with its own mip chain) stored in the file.
+    pub fn surface_count(&self) -> u32 {
+        let faces = match (&self.dx10, self.is_cubemap()) {
+            (_, false) => 1,
+            (Some(_), true) => 6,
+            (None, true) => (self.caps2 & DDSCAPS2_CUBEMAP_ALL_FACES).count_ones(),
+        };
+
+        faces.saturating_mul(self.array_size())
+    }
+
+    /// Size of mip level `mip` of a single surface, including all of its depth slices.
+    pub fn mip_size(&self, mip: u32) -> Option<usize> {
+        let (width, height, depth) = self.mip_dimensions(mip);
+        self.format()
+            .surface_size(width, height)?
+            .checked_mul(depth as usize)
+    }
+
+    pub fn mip_dimensions(&self, mip: u32) -> (u32, u32, u32) {
+        (
+            self.width.checked_shr(mip).unwrap_or(0).max(1),
+            self.height.checked_shr(mip).unwrap_or(0).max(1),
+            self.depth().checked_shr(mip).unwrap_or(0).max(1),
+        )
+    }
+
+    /// Total size of the surfaces declared by the header,
+    /// or `None` if the format is unknown or the size does not fit in a `usize`.
+    pub fn payload_size(&self) -> Option<usize> {
+        let chain = (0..self.mip_count())
+            .try_fold(0usize, |size, mip| size.checked_add(self.mip_size(mip)?))?;
+
+        chain.checked_mul(self.surface_count() as usize)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DdsError {
+    TooShort(usize),
+    InvalidMagic,
+    InvalidHeaderSize(u32),
+    NoSurfaces,
+    UnsupportedFormat(DdsFormat),
+    TruncatedPayload { expected: usize, actual: usize },
+    InvalidImageSize { width: u32, height: u32 },
+    MissingSource,
+    Png(String),
+}
+
+impl fmt::Display for DdsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DdsError::TooShort(len) => {
+                write!(f, "file is too short for a DDS header ({} bytes)", len)
+            }
+            DdsError::InvalidMagic => write!(f, "file does not start with the DDS magic"),
+            DdsError::InvalidHeaderSize(size) => write!(f, "invalid DDS header size {}", size),
+            DdsError::NoSurfaces => write!(f, "cubemap header does not declare any faces"),
+            DdsError::UnsupportedFormat(format) => {
+                write!(f, "{:?} surfaces cannot be converted", format)
+            }
+            DdsError::TruncatedPayload { expected, actual } => write!(
+                f,
+                "payload is {} bytes, but the header declares {} bytes",
+                actual, expected
+            ),
+            DdsError::InvalidImageSize { width, height } => write!(
+                f,
+                "{}x{} image does not match the layout of the texture",
+                width, height
+            ),
+            DdsError::MissingSource => write!(
+                f,
+                "PNG has no embedded DDS file, import it into the original texture with import_png"
+            ),
+            DdsError::Png(error) => write!(f, "PNG: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for DdsError {}
+
+impl From<png::EncodingError> for DdsError {
+    fn from(error: png::EncodingError) -> Self {
+        DdsError::Png(error.to_string())
+    }
+}
+
+impl From<png::DecodingError> for DdsError {
+    fn from(error: png::DecodingError) -> Self {
+        DdsError::Png(error.to_string())
+    }
+}
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct DdsFile {
     pub file: Vec<u8>,
+
+    #[serde(skip)]
+    pub header: Option<DdsHeader>,
+}
+
+impl DdsFile {
+    /// Surface data following the headers.
+    pub fn payload(&self) -> &[u8] {
+        match &self.header {
+            Some(header) => self.file.get(header.data_offset()..).unwrap_or_default(),
+            None => &[],
+        }
+    }
+
+    fn parsed_header(&self) -> Result<DdsHeader, DdsError> {
+        match &self.header {
+            Some(header) => Ok(header.clone()),
+            None => DdsHeader::parse(&self.file),
+        }
+    }
+
+    /// Whether the surfaces can be decoded with `to_rgba`.
+    pub fn is_convertible(&self) -> bool {
+        self.header.as_ref().is_some_and(|header| {
+            header.format().is_convertible()
+                && header.surface_count() != 0
+                && header
+                    .payload_size()
+                    .is_some_and(|size| self.payload().len() >= size)
+        })
+    }
+
+    /// Decodes the top mip of every surface into RGBA8 pixels.
+    /// Cubemap faces, array layers and depth slices are stacked vertically, in the order they are stored.
+    pub fn to_rgba(&self) -> Result<(u32, u32, Vec<u8>), DdsError> {
+        let header = self.parsed_header()?;
+        let format = header.format();
+        if !format.is_convertible() {
+            return Err(DdsError::UnsupportedFormat(format));
+        }
+
+        if header.surface_count() == 0 {
+            return Err(DdsError::NoSurfaces);
+        }
+
+        let expected = header.payload_size().ok_or(DdsError::InvalidImageSize {
+            width: header.width,
+            height: header.height,
+        })?;
+        let payload = self.payload();
+        if payload.len() < expected {
+            return Err(DdsError::TruncatedPayload {
+                expected,
+                actual: payload.len(),
+            });
+        }
+
+        let (width, height) = (header.width.max(1), header.height.max(1));
+        let chain_size = expected / header.surface_count() as usize;
+        let slice_size = format.surface_size(width, height).unwrap();
+
+        let mut rgba = Vec::new();
+        for surface in 0..header.surface_count() as usize {
+            for slice in 0..header.depth() as usize {
+                let offset = surface * chain_size + slice * slice_size;
+                rgba.extend(
+                    decode_surface(
+                        format,
+                        &payload[offset..offset + slice_size],
+                        width as usize,
+                        height as usize,
+                    )
+                    .unwrap(),
+                );
+            }
+        }
+
+        let layers = header.surface_count() * header.depth();
+        Ok((width, height * layers, rgba))
+    }
+
+    /// Encodes RGBA8 pixels laid out like the output of `to_rgba` into the pixel format of the header,
+    /// regenerating the same number of mips. The texture is resized if the dimensions differ.
+    /// Bytes following the surfaces are kept.
+    pub fn encode_rgba(&mut self, width: u32, height: u32, rgba: &[u8]) -> Result<(), DdsError> {
+        let mut header = self.parsed_header()?;
+        let format = header.format();
+        if !format.is_convertible() {
+            return Err(DdsError::UnsupportedFormat(format));
+        }
+
+        if header.surface_count() == 0 {
+            return Err(DdsError::NoSurfaces);
+        }
+
+        let trailing = header
+            .payload_size()
+            .and_then(|size| self.payload().get(size..))
+            .unwrap_or_default()
+            .to_vec();
+
+        let layers = header.surface_count().saturating_mul(header.depth());
+        if width == 0
+            || height == 0
+            || height % layers != 0
+            || rgba.len() != width as usize * height as usize * 4
+        {
+            return Err(DdsError::InvalidImageSize { width, height });
+        }
+
+        header.width = width;
+        header.height = height / layers;
+
+        header.mip_count = header.mip_count.min(header.max_mip_count());
+        header.pitch_or_linear_size = match format.block_size() {
+            Some(_) => format.surface_size(header.width, header.height).unwrap(),
+            None => (width as usize * format.bits_per_pixel().unwrap()).div_ceil(8),
+        } as u32;
+
+        let dimensions = (
+            header.width as usize,
+            header.height as usize,
+            header.depth() as usize,
+        );
+
+        let mut payload = Vec::new();
+        for surface in rgba.chunks_exact(dimensions.0 * dimensions.1 * dimensions.2 * 4) {
+            let (mut pixels, mut dimensions) = (surface.to_vec(), dimensions);
+
+            for mip in 0..header.mip_count() {
+                if mip != 0 {
+                    (pixels, dimensions) = downsample(&pixels, dimensions);
+                }
+
+                for slice in pixels.chunks_exact(dimensions.0 * dimensions.1 * 4) {
+                    payload
+                        .extend(encode_surface(format, slice, dimensions.0, dimensions.1).unwrap());
+                }
+            }
+        }
+
+        let mut file = self.file[..header.data_offset()].to_vec();
+        header.write_to(&mut file);
+        file.extend(payload);
+        file.extend(trailing);
+
+        self.file = file;
+        self.header = Some(header);
+        Ok(())
+    }
+
+    /// Exports the texture as a PNG, embedding the DDS file so it can be converted back with `from_png`.
+    pub fn to_png(&self) -> Result<Vec<u8>, DdsError> {
+        let (width, height, rgba) = self.to_rgba()?;
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        // Latin-1 maps every byte to one character, so the file is stored as is
+        let source = self.file.iter().map(|&byte| byte as char).collect();
+        encoder.add_ztxt_chunk(PNG_SOURCE_KEYWORD.to_string(), source)?;
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&rgba)?;
+        writer.finish()?;
+
+        Ok(png)
+    }
+
+    /// Whether the surfaces decode to exactly the given pixels, in which case re-encoding them is unnecessary.
+    fn matches_rgba(&self, width: u32, height: u32, rgba: &[u8]) -> bool {
+        self.to_rgba()
+            .is_ok_and(|decoded| decoded.0 == width && decoded.1 == height && decoded.2 == rgba)
+    }
+
+    /// Replaces the surfaces with the contents of a PNG, keeping the pixel format and mip layout.
+    /// The file is left untouched if the pixels are unchanged.
+    pub fn import_png(&mut self, data: &[u8]) -> Result<(), DdsError> {
+        let image = PngImage::read(data)?;
+        if self.matches_rgba(image.width, image.height, &image.rgba) {
+            return Ok(());
+        }
+
+        self.encode_rgba(image.width, image.height, &image.rgba)
+    }
+
+    /// Creates a texture from a PNG exported by `to_png`.
+    /// If the pixels were not edited, the embedded DDS file is returned as it was,
+    /// otherwise the pixels are encoded with its header.
+    /// PNGs without an embedded DDS file (e.g. saved by an editor that drops text chunks)
+    /// return `DdsError::MissingSource`; use `import_png` on the original texture for those.
+    pub fn from_png(data: &[u8]) -> Result<Self, DdsError> {
+        let image = PngImage::read(data)?;
+
+        let mut dds = image
+            .dds_file
+            .map(|file| Self::from(file.as_slice()))
+            .filter(|source| source.header.is_some())
+            .ok_or(DdsError::MissingSource)?;
+
+        if !dds.matches_rgba(image.width, image.height, &image.rgba) {
+            dds.encode_rgba(image.width, image.height, &image.rgba)?;
+        }
+
+        Ok(dds)
+    }
+}
+
+struct PngImage {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+
+    /// DDS file embedded by `DdsFile::to_png`
+    dds_file: Option<Vec<u8>>,
+}
+
+impl PngImage {
+    fn read(data: &[u8]) -> Result<Self, DdsError> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+
+        let dds_file = reader
+            .info()
+            .compressed_latin1_text
+            .iter()
+            .find(|chunk| chunk.keyword == PNG_SOURCE_KEYWORD)
+            .and_then(|chunk| chunk.get_text().ok())
+            .and_then(|text| text.chars().map(|c| u8::try_from(c).ok()).collect());
+
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buffer)?;
+
+        let rgba = buffer[..frame.buffer_size()]
+            .chunks_exact(frame.color_type.samples())
+            .flat_map(|pixel| match pixel.len() {
+                1 => [pixel[0], pixel[0], pixel[0], 0xFF],
+                2 => [pixel[0], pixel[0], pixel[0], pixel[1]],
+                3 => [pixel[0], pixel[1], pixel[2], 0xFF],
+                _ => [pixel[0], pixel[1], pixel[2], pixel[3]],
+            })
+            .collect();
+
+        Ok(Self {
+            width: frame.width,
+            height: frame.height,
+            rgba,
+            dds_file,
+        })
+    }
 }
 
 impl NuccBinaryParsed for DdsFile {
@@ -13,20 +744,108 @@ impl NuccBinaryParsed for DdsFile {
         NuccBinaryType::DDS
     }
 
-    fn extension(&self, _: bool) -> String {
-        String::from(".dds")
+    fn extension(&self, use_json: bool) -> String {
+        if use_json && self.is_convertible() {
+            String::from(".png")
+        } else {
+            String::from(".dds")
+        }
     }
 
-    fn serialize(&self, _: bool) -> Vec<u8> {
-        self.file.clone()
+    fn serialize(&self, use_json: bool) -> Vec<u8> {
+        if use_json && self.is_convertible() {
+            self.to_png().unwrap()
+        } else {
+            self.file.clone()
+        }
     }
 
-    fn deserialize(data: &[u8], _: bool) -> Self
+    fn deserialize(data: &[u8], use_json: bool) -> Self
     where
         Self: Sized,
     {
-        Self {
-            file: data.to_vec(),
+        if use_json && data.starts_with(PNG_SIGNATURE) {
+            // PNGs that cannot be converted are kept as they are, so `validate` reports them
+            // instead of panicking; use `from_png` to get the error
+            if let Ok(dds) = Self::from_png(data) {
+                return dds;
+            }
+        }
+
+        Self::from(data)
+    }
+
+    fn info(&self) -> serde_json::Value {
+        let header = match &self.header {
+            Some(header) => header,
+            None => {
+                return json!({
+                    "type": self.binary_type().to_string(),
+                    "size": self.file.len(),
+                    "error": DdsHeader::parse(&self.file).err().map(|e| e.to_string()),
+                })
+            }
+        };
+
+        json!({
+            "type": self.binary_type().to_string(),
+            "size": self.file.len(),
+            "width": header.width,
+            "height": header.height,
+            "depth": header.depth(),
+            "mip_count": header.mip_count(),
+            "format": header.format(),
+            "four_cc": header.pixel_format.four_cc_string(),
+            "dxgi_format": header.dx10.as_ref().map(|dx10| dx10.dxgi_format),
+            "cubemap": header.is_cubemap(),
+            "array_size": header.array_size(),
+            "payload_size": self.payload().len(),
+            "expected_payload_size": header.payload_size(),
+            "header": header,
+        })
+    }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let header = match DdsHeader::parse(&self.file) {
+            Ok(header) => header,
+            Err(e) => {
+                // A PNG that `deserialize` could not convert
+                let error = if self.file.starts_with(PNG_SIGNATURE) {
+                    Self::from_png(&self.file).err().unwrap_or(e)
+                } else {
+                    e
+                };
+
+                return vec![Diagnostic::error(String::from("file"), error.to_string())];
+            }
+        };
+
+        let actual = self.file.len().saturating_sub(header.data_offset());
+        match header.payload_size() {
+            None if header.format() == DdsFormat::Unknown => vec![Diagnostic::warning(
+                String::from("header"),
+                String::from("unknown pixel format, cannot check the payload size"),
+            )],
+            None => vec![Diagnostic::error(
+                String::from("header"),
+                String::from("declared surfaces are too large to address"),
+            )],
+            Some(expected) if actual < expected => vec![Diagnostic::error(
+                String::from("file"),
+                format!(
+                    "payload is {} bytes, but the header declares {} bytes",
+                    actual, expected
+                ),
+            )],
+            Some(expected) if actual > expected => vec![Diagnostic::warning(
+                String::from("file"),
+                format!(
+                    "payload is {} bytes, which is {} bytes more than the header declares",
+                    actual,
+                    actual - expected
+                ),
+            )],
+            Some(_) => vec![],
         }
     }
 }
@@ -35,6 +854,7 @@ impl From<&[u8]> for DdsFile {
     fn from(data: &[u8]) -> Self {
         Self {
             file: data.to_vec(),
+            header: DdsHeader::parse(data).ok(),
         }
     }
 }
@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+
+pub const MAX_KEY_LEN: usize = 32;
+
+/// Describes how Ev strings are encrypted: each byte is XORed with a repeating key,
+/// and the bytes of each block are optionally reversed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "CipherRepr", into = "CipherRepr")]
+pub struct Cipher {
+    key: [u8; MAX_KEY_LEN],
+    key_len: usize,
+    block_size: usize,
+    reverse_blocks: bool,
+}
+
+impl Cipher {
+    /// Used by JoJo games
+    pub const JOJO: Cipher =
+        Cipher::from_static(b"\x8C\x91\x9B\x9A\x89\xD1\x87\x99\x9D\x96\x91", 4, true);
+
+    const fn from_static(key: &[u8], block_size: usize, reverse_blocks: bool) -> Self {
+        assert!(!key.is_empty() && key.len() <= MAX_KEY_LEN && block_size != 0);
+
+        let mut array = [0; MAX_KEY_LEN];
+        let mut i = 0;
+        while i < key.len() {
+            array[i] = key[i];
+            i += 1;
+        }
+
+        Self {
+            key: array,
+            key_len: key.len(),
+            block_size,
+            reverse_blocks,
+        }
+    }
+
+    /// Returns `None` if the key is empty or longer than `MAX_KEY_LEN`, or if the block size is 0.
+    pub fn new(key: &[u8], block_size: usize, reverse_blocks: bool) -> Option<Self> {
+        if key.is_empty() || key.len() > MAX_KEY_LEN || block_size == 0 {
+            return None;
+        }
+
+        Some(Self::from_static(key, block_size, reverse_blocks))
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key[..self.key_len]
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn reverse_blocks(&self) -> bool {
+        self.reverse_blocks
+    }
+
+    /// Position that the byte at `index` is moved to by the block reversal.
+    fn reordered_index(&self, index: usize, len: usize) -> usize {
+        if !self.reverse_blocks {
+            return index;
+        }
+
+        let block_start = index - (index % self.block_size);
+        let block_end = (block_start + self.block_size).min(len);
+        block_start + (block_end - 1 - index)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Vec<u8> {
+        let mut result = vec![0; data.len()];
+
+        for (i, (byte, key)) in data.iter().zip(self.key().iter().cycle()).enumerate() {
+            result[self.reordered_index(i, data.len())] = byte ^ key;
+        }
+
+        result
+    }
+
+    pub fn encrypt(&self, data: &[u8]) -> Vec<u8> {
+        let mut result = vec![0; data.len()];
+
+        for (i, key) in (0..data.len()).zip(self.key().iter().cycle()) {
+            result[i] = data[self.reordered_index(i, data.len())] ^ key;
+        }
+
+        result
+    }
+
+    /// Recovers a key of `key_len` bytes from encrypted samples and their partially known plaintext.
+    /// Each known plaintext byte votes for a key byte, and the most voted byte wins.
+    /// Returns `None` if a key byte did not get any votes.
+    pub fn recover_key(
+        samples: &[(&[u8], &[Option<u8>])],
+        key_len: usize,
+        block_size: usize,
+        reverse_blocks: bool,
+    ) -> Option<Self> {
+        // Only the block scheme matters for finding where each byte ends up
+        let scheme = Self::new(&[0], block_size, reverse_blocks)?;
+
+        let mut votes = vec![[0usize; 0x100]; key_len];
+        for (encrypted, known) in samples.iter() {
+            for (i, byte) in encrypted.iter().enumerate() {
+                if let Some(Some(plain)) = known.get(scheme.reordered_index(i, encrypted.len())) {
+                    votes[i % key_len][(byte ^ plain) as usize] += 1;
+                }
+            }
+        }
+
+        let key = votes
+            .iter()
+            .map(|counts| {
+                let (byte, count) = counts
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, count)| **count)
+                    .unwrap();
+                (*count != 0).then_some(byte as u8)
+            })
+            .collect::<Option<Vec<u8>>>()?;
+
+        Self::new(&key, block_size, reverse_blocks)
+    }
+}
+
+impl Default for Cipher {
+    fn default() -> Self {
+        Cipher::JOJO
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CipherRepr {
+    #[serde(with = "hex::serde")]
+    key: Vec<u8>,
+    block_size: usize,
+    reverse_blocks: bool,
+}
+
+impl From<Cipher> for CipherRepr {
+    fn from(cipher: Cipher) -> Self {
+        Self {
+            key: cipher.key().to_vec(),
+            block_size: cipher.block_size,
+            reverse_blocks: cipher.reverse_blocks,
+        }
+    }
+}
+
+impl TryFrom<CipherRepr> for Cipher {
+    type Error = String;
+
+    fn try_from(repr: CipherRepr) -> Result<Self, Self::Error> {
+        Cipher::new(&repr.key, repr.block_size, repr.reverse_blocks).ok_or_else(|| {
+            format!(
+                "Invalid Ev cipher: key must be 1 to {} bytes long and block size must not be 0",
+                MAX_KEY_LEN
+            )
+        })
+    }
+}
@@ -1,20 +1,39 @@
 use super::endian_from_bool;
+use super::ev_cipher::Cipher;
 use super::NuccBinaryParsed;
 use super::NuccBinaryType;
+use crate::utils::{fit_fixed_string, fixed_string_bytes, FixedStringOverflow, OverflowPolicy};
+use crate::validation::{check_float, check_string, Diagnostic};
 
-use deku::bitvec::{BitSlice, BitVec, Msb0};
+use deku::bitvec::{BitSlice, BitVec, BitView, Msb0};
 use deku::ctx::{Endian, Limit};
 use deku::prelude::*;
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 use strum_macros::EnumMessage;
 use strum_macros::{Display, EnumIter, EnumString};
 
-#[derive(Clone, Copy, EnumIter, Display, EnumString, EnumMessage, Serialize, Deserialize)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    EnumIter,
+    Display,
+    EnumString,
+    EnumMessage,
+    Serialize,
+    Deserialize,
+)]
 pub enum Version {
     /// JoJo
     Encrypted,
     /// Storm
     Unencrypted,
+    /// Detect automatically
+    Auto,
+    /// Custom cipher
+    Custom(Cipher),
 }
 
 impl Default for Version {
@@ -23,7 +42,33 @@ impl Default for Version {
     }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+impl Version {
+    /// Returns `None` if the strings are not encrypted.
+    pub fn cipher(&self) -> Option<Cipher> {
+        match self {
+            Version::Encrypted => Some(Cipher::JOJO),
+            Version::Unencrypted | Version::Auto => None,
+            Version::Custom(cipher) => Some(*cipher),
+        }
+    }
+
+    /// Versions that can be detected, which excludes `Auto` and `Custom`.
+    pub fn builtin() -> impl Iterator<Item = Version> {
+        Version::iter().filter(|version| !matches!(version, Version::Auto | Version::Custom(_)))
+    }
+}
+
+const AUTO_VERSION: &str = "Ev version must be detected before reading or writing";
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct VersionDetection {
+    pub version: Version,
+
+    /// Between 0 and 1, based on how much better the strings decode than with the other versions
+    pub confidence: f32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[deku_derive(DekuRead, DekuWrite)]
 #[deku(
     endian = "endian",
@@ -33,7 +78,7 @@ impl Default for Version {
 pub struct Entry {
     #[deku(
         reader = "Entry::decrypt(deku::rest, version)",
-        writer = "Entry::encrypt(deku::output, version, &self.sound_name)"
+        writer = "Entry::encrypt(deku::output, version, \"sound_name\", &self.sound_name)"
     )]
     pub sound_name: String,
 
@@ -50,17 +95,17 @@ pub struct Entry {
 
     #[deku(
         reader = "Entry::decrypt(deku::rest, version)",
-        writer = "Entry::encrypt(deku::output, version, &self.xfbin_path)"
+        writer = "Entry::encrypt(deku::output, version, \"xfbin_path\", &self.xfbin_path)"
     )]
     pub xfbin_path: String,
     #[deku(
         reader = "Entry::decrypt(deku::rest, version)",
-        writer = "Entry::encrypt(deku::output, version, &self.anm_name)"
+        writer = "Entry::encrypt(deku::output, version, \"anm_name\", &self.anm_name)"
     )]
     pub anm_name: String,
     #[deku(
         reader = "Entry::decrypt(deku::rest, version)",
-        writer = "Entry::encrypt(deku::output, version, &self.target_bone)"
+        writer = "Entry::encrypt(deku::output, version, \"target_bone\", &self.target_bone)"
     )]
     pub target_bone: String,
 
@@ -75,55 +120,28 @@ pub struct Entry {
 
     #[deku(
         reader = "Entry::decrypt(deku::rest, version)",
-        writer = "Entry::encrypt(deku::output, version, &self.anm_command)"
+        writer = "Entry::encrypt(deku::output, version, \"anm_command\", &self.anm_command)"
     )]
     pub anm_command: String,
 }
 
 impl Entry {
-    fn xor(data: &[u8], decrypt: bool) -> Vec<u8> {
-        let mut key = b"\x8C\x91\x9B\x9A\x89\xD1\x87\x99\x9D\x96\x91"
-            .iter()
-            .cycle();
-
-        let mut block = vec![];
-        let mut result = vec![];
-
-        if decrypt {
-            for byte in data {
-                block.push(byte ^ key.next().unwrap());
-
-                if block.len() == 4 {
-                    result.extend(block.into_iter().rev());
-                    block = vec![];
-                }
-            }
-        } else {
-            for byte in data {
-                block.push(*byte);
-
-                if block.len() == 4 {
-                    result.extend(block.into_iter().rev().map(|b| b ^ key.next().unwrap()));
-                    block = vec![];
-                }
-            }
-        }
-
-        result
-    }
-
     fn decrypt(
         input: &BitSlice<Msb0, u8>,
         version: Version,
     ) -> Result<(&BitSlice<Msb0, u8>, String), DekuError> {
-        let (rest, data) = Vec::<u8>::read(input, Limit::from(0x20)).unwrap();
+        let (rest, data) = Vec::<u8>::read(input, Limit::from(0x20))?;
 
-        let decrypted = match version {
-            Version::Encrypted => Entry::xor(&data, true),
-            Version::Unencrypted => data,
+        if version == Version::Auto {
+            return Err(DekuError::InvalidParam(String::from(AUTO_VERSION)));
+        }
+
+        let decrypted = match version.cipher() {
+            Some(cipher) => cipher.decrypt(&data),
+            None => data,
         };
-        let string =
-            String::from_utf8(decrypted.into_iter().take_while(|b| *b != 0).collect()).unwrap();
+        let string = String::from_utf8(decrypted.into_iter().take_while(|b| *b != 0).collect())
+            .map_err(|e| DekuError::Parse(format!("Invalid string for Ev {}: {}", version, e)))?;
 
         Ok((rest, string))
     }
@@ -131,19 +149,45 @@ impl Entry {
     fn encrypt(
         output: &mut BitVec<Msb0, u8>,
         version: Version,
+        field: &str,
         string: &str,
     ) -> Result<(), DekuError> {
-        let string = string.to_string() + &String::from("\0").repeat(0x20 - string.len());
+        let mut data = fixed_string_bytes(field, string, 0x20)?;
+        data.resize(0x20, 0);
+
+        if version == Version::Auto {
+            return Err(DekuError::InvalidParam(String::from(AUTO_VERSION)));
+        }
 
-        let encrypted = match version {
-            Version::Encrypted => Entry::xor(string.as_bytes(), false),
-            Version::Unencrypted => string.as_bytes().to_vec(),
+        let encrypted = match version.cipher() {
+            Some(cipher) => cipher.encrypt(&data),
+            None => data,
         };
         encrypted.write(output, ())
     }
+
+    fn strings(&self) -> [(&'static str, &String); 5] {
+        [
+            ("sound_name", &self.sound_name),
+            ("xfbin_path", &self.xfbin_path),
+            ("anm_name", &self.anm_name),
+            ("target_bone", &self.target_bone),
+            ("anm_command", &self.anm_command),
+        ]
+    }
+
+    fn strings_mut(&mut self) -> [(&'static str, &mut String); 5] {
+        [
+            ("sound_name", &mut self.sound_name),
+            ("xfbin_path", &mut self.xfbin_path),
+            ("anm_name", &mut self.anm_name),
+            ("target_bone", &mut self.target_bone),
+            ("anm_command", &mut self.anm_command),
+        ]
+    }
 }
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[deku_derive(DekuRead, DekuWrite)]
 #[deku(
     endian = "endian",
@@ -184,4 +228,161 @@ impl NuccBinaryParsed for EvFile {
     {
         serde_json::from_slice(data).unwrap()
     }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            for (field, string) in [
+                ("sound_name", &entry.sound_name),
+                ("anm_name", &entry.anm_name),
+            ] {
+                check_string(
+                    &mut diagnostics,
+                    format!("entries[{}].{}", i, field),
+                    string,
+                    0x20,
+                );
+            }
+
+            // These can be left empty, so only check their length
+            for (field, string) in [
+                ("xfbin_path", &entry.xfbin_path),
+                ("target_bone", &entry.target_bone),
+                ("anm_command", &entry.anm_command),
+            ] {
+                if !string.is_empty() {
+                    check_string(
+                        &mut diagnostics,
+                        format!("entries[{}].{}", i, field),
+                        string,
+                        0x20,
+                    );
+                }
+            }
+
+            check_float(
+                &mut diagnostics,
+                format!("entries[{}].volume", i),
+                entry.volume,
+            );
+            check_float(&mut diagnostics, format!("entries[{}].unk3", i), entry.unk3);
+            check_float(&mut diagnostics, format!("entries[{}].unk4", i), entry.unk4);
+        }
+
+        diagnostics
+    }
+}
+
+impl EvFile {
+    /// Character code of an `_ev.bin` path, taken from the start of its file name (`1dio01_ev.bin`).
+    pub fn chara_code_of_path(path: &str) -> Option<&str> {
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        file_name
+            .strip_suffix("_ev.bin")
+            .filter(|code| !code.is_empty())
+    }
+
+    /// Tries every version and picks the one whose strings look like valid paths and bone names.
+    pub fn detect_version(data: &[u8], endian: Endian) -> VersionDetection {
+        Self::detect_version_among(data, endian, Version::builtin())
+    }
+
+    /// Same as `detect_version`, but only tries the given versions, such as `Custom` ciphers
+    /// recovered from other titles. `Auto` is skipped, and the default version is returned
+    /// with no confidence if nothing else is left.
+    pub fn detect_version_among(
+        data: &[u8],
+        endian: Endian,
+        versions: impl IntoIterator<Item = Version>,
+    ) -> VersionDetection {
+        let mut scores: Vec<(Version, f32)> = versions
+            .into_iter()
+            .filter(|version| *version != Version::Auto)
+            .map(|version| {
+                let score = EvFile::read(data.view_bits(), (endian, version))
+                    .map_or(0.0, |(_, ev)| ev.string_score());
+                (version, score)
+            })
+            .collect();
+
+        // Stable sort keeps the default version first when scores are equal
+        scores.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let (version, best) = scores.first().copied().unwrap_or_default();
+        let runner_up = scores.get(1).map_or(0.0, |(_, score)| *score);
+
+        VersionDetection {
+            version,
+            confidence: best - runner_up,
+        }
+    }
+
+    /// Recovers the cipher of an encrypted Ev file, assuming most of each string field is zero padding.
+    pub fn recover_cipher(
+        data: &[u8],
+        endian: Endian,
+        key_len: usize,
+        block_size: usize,
+        reverse_blocks: bool,
+    ) -> Option<Cipher> {
+        const ENTRY_SIZE: usize = 0xCA;
+        const STRING_OFFSETS: [usize; 5] = [0x00, 0x36, 0x56, 0x76, 0xAA];
+
+        let (_, count) = u16::read(data.view_bits(), endian).ok()?;
+
+        let fields: Vec<&[u8]> = (0..count as usize)
+            .map(|i| 2 + (ENTRY_SIZE * i))
+            .flat_map(|entry| STRING_OFFSETS.iter().map(move |offset| entry + offset))
+            .filter_map(|offset| data.get(offset..offset + 0x20))
+            .collect();
+
+        let padding = [Some(0); 0x20];
+        let samples: Vec<(&[u8], &[Option<u8>])> = fields
+            .into_iter()
+            .map(|field| (field, padding.as_slice()))
+            .collect();
+
+        Cipher::recover_key(&samples, key_len, block_size, reverse_blocks)
+    }
+
+    /// Fraction of non-empty strings that only contain printable ASCII characters.
+    fn string_score(&self) -> f32 {
+        let (mut printable, mut total) = (0, 0);
+
+        for entry in self.entries.iter() {
+            for (_, string) in entry.strings() {
+                if !string.is_empty() {
+                    total += 1;
+                    if string.bytes().all(|b| (0x20..0x7F).contains(&b)) {
+                        printable += 1;
+                    }
+                }
+            }
+        }
+
+        if total == 0 {
+            0.0
+        } else {
+            printable as f32 / total as f32
+        }
+    }
+
+    /// Makes every string fit in its 0x20 byte field according to `policy`.
+    /// Returns the strings that were truncated.
+    pub fn fit_strings(
+        &mut self,
+        policy: OverflowPolicy,
+    ) -> Result<Vec<FixedStringOverflow>, FixedStringOverflow> {
+        let mut truncated = Vec::new();
+
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            for (field, string) in entry.strings_mut() {
+                let field = format!("entries[{}].{}", i, field);
+                truncated.extend(fit_fixed_string(&field, string, 0x20, policy)?);
+            }
+        }
+
+        Ok(truncated)
+    }
 }
@@ -0,0 +1,330 @@
+use super::ev_file::{Entry, EvFile};
+
+use hashbrown::HashSet;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+const CSV_HEADER: [&str; 6] = [
+    "index",
+    "anm_name",
+    "timing",
+    "sound_name",
+    "volume",
+    "target_bone",
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cue {
+    /// Index of the entry in the Ev file, or `None` for cues that should be added
+    #[serde(default)]
+    pub index: Option<usize>,
+
+    pub timing: i16,
+    pub sound_name: String,
+    pub volume: f32,
+    pub target_bone: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnimationTimeline {
+    pub anm_name: String,
+    pub cues: Vec<Cue>,
+}
+
+#[derive(Debug)]
+pub enum TimelineError {
+    Csv { line: usize, message: String },
+    Json(serde_json::Error),
+    UnknownIndex(usize),
+    DuplicateIndex(usize),
+}
+
+impl fmt::Display for TimelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimelineError::Csv { line, message } => write!(f, "CSV line {}: {}", line, message),
+            TimelineError::Json(error) => write!(f, "JSON: {}", error),
+            TimelineError::UnknownIndex(index) => write!(f, "entry {} does not exist", index),
+            TimelineError::DuplicateIndex(index) => {
+                write!(f, "entry {} is used by more than one cue", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TimelineError {}
+
+impl From<serde_json::Error> for TimelineError {
+    fn from(error: serde_json::Error) -> Self {
+        TimelineError::Json(error)
+    }
+}
+
+impl Cue {
+    fn from_entry(index: usize, entry: &Entry) -> Self {
+        Self {
+            index: Some(index),
+            timing: entry.timing,
+            sound_name: entry.sound_name.clone(),
+            volume: entry.volume,
+            target_bone: entry.target_bone.clone(),
+        }
+    }
+
+    fn apply(&self, anm_name: &str, entry: &mut Entry) {
+        entry.anm_name = anm_name.to_string();
+        entry.timing = self.timing;
+        entry.sound_name = self.sound_name.clone();
+        entry.volume = self.volume;
+        entry.target_bone = self.target_bone.clone();
+    }
+}
+
+impl EvFile {
+    /// Returns the entries of an animation along with their indices, sorted by timing.
+    pub fn cues_for_animation(&self, anm_name: &str) -> Vec<(usize, &Entry)> {
+        let mut cues: Vec<(usize, &Entry)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.anm_name == anm_name)
+            .collect();
+
+        cues.sort_by_key(|(_, entry)| entry.timing);
+        cues
+    }
+
+    /// Groups the entries by animation, in the order the animations first appear.
+    pub fn timeline(&self) -> Vec<AnimationTimeline> {
+        let mut anm_names: Vec<&str> = Vec::new();
+        for entry in self.entries.iter() {
+            if !anm_names.contains(&entry.anm_name.as_str()) {
+                anm_names.push(&entry.anm_name);
+            }
+        }
+
+        anm_names
+            .into_iter()
+            .map(|anm_name| AnimationTimeline {
+                anm_name: anm_name.to_string(),
+                cues: self
+                    .cues_for_animation(anm_name)
+                    .into_iter()
+                    .map(|(i, entry)| Cue::from_entry(i, entry))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub fn timeline_json(&self) -> String {
+        serde_json::to_string_pretty(&self.timeline()).unwrap()
+    }
+
+    pub fn timeline_csv(&self) -> String {
+        let mut csv = CSV_HEADER.join(",") + "\n";
+
+        for timeline in self.timeline() {
+            for cue in timeline.cues {
+                let row = [
+                    cue.index.map(|i| i.to_string()).unwrap_or_default(),
+                    timeline.anm_name.clone(),
+                    cue.timing.to_string(),
+                    cue.sound_name,
+                    cue.volume.to_string(),
+                    cue.target_bone,
+                ];
+
+                csv += &row.map(|field| escape_csv(&field)).join(",");
+                csv += "\n";
+            }
+        }
+
+        csv
+    }
+
+    /// Applies an edited timeline to the entries, returning the indices of the entries that were removed.
+    ///
+    /// Cues with an index update that entry in place, cues without one are appended as new entries,
+    /// and entries that are not referenced by any cue are removed.
+    /// Entries keep their original order, so unchanged cues stay where they were.
+    ///
+    /// New entries copy the fields a cue does not cover from an entry of the same animation,
+    /// or are zeroed if the animation has none.
+    pub fn import_timeline(
+        &mut self,
+        timeline: &[AnimationTimeline],
+    ) -> Result<Vec<usize>, TimelineError> {
+        let mut used = HashSet::new();
+        for cue in timeline.iter().flat_map(|t| t.cues.iter()) {
+            if let Some(index) = cue.index {
+                if index >= self.entries.len() {
+                    return Err(TimelineError::UnknownIndex(index));
+                }
+
+                if !used.insert(index) {
+                    return Err(TimelineError::DuplicateIndex(index));
+                }
+            }
+        }
+
+        for timeline in timeline.iter() {
+            for cue in timeline.cues.iter() {
+                if let Some(index) = cue.index {
+                    cue.apply(&timeline.anm_name, &mut self.entries[index]);
+                }
+            }
+        }
+
+        let removed: Vec<usize> = (0..self.entries.len())
+            .filter(|index| !used.contains(index))
+            .collect();
+
+        let mut index = 0;
+        self.entries.retain(|_| {
+            index += 1;
+            used.contains(&(index - 1))
+        });
+
+        for timeline in timeline.iter() {
+            for cue in timeline.cues.iter().filter(|cue| cue.index.is_none()) {
+                let mut entry = self
+                    .entries
+                    .iter()
+                    .find(|entry| entry.anm_name == timeline.anm_name)
+                    .cloned()
+                    .unwrap_or_else(|| Entry {
+                        unk2: vec![0; 3],
+                        ..Default::default()
+                    });
+
+                cue.apply(&timeline.anm_name, &mut entry);
+                self.entries.push(entry);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    pub fn import_timeline_json(&mut self, data: &[u8]) -> Result<Vec<usize>, TimelineError> {
+        let timeline: Vec<AnimationTimeline> = serde_json::from_slice(data)?;
+        self.import_timeline(&timeline)
+    }
+
+    pub fn import_timeline_csv(&mut self, data: &str) -> Result<Vec<usize>, TimelineError> {
+        let mut timeline: Vec<AnimationTimeline> = Vec::new();
+
+        let mut rows = parse_csv(data)?.into_iter();
+        match rows.next() {
+            Some((_, header)) if header == CSV_HEADER => {}
+            Some((line, _)) => {
+                return Err(TimelineError::Csv {
+                    line,
+                    message: format!("expected header `{}`", CSV_HEADER.join(",")),
+                })
+            }
+            None => return self.import_timeline(&timeline),
+        }
+
+        for (line, row) in rows {
+            let error = |message: String| TimelineError::Csv { line, message };
+
+            if row.len() != CSV_HEADER.len() {
+                return Err(error(format!(
+                    "expected {} fields, found {}",
+                    CSV_HEADER.len(),
+                    row.len()
+                )));
+            }
+
+            let parse_error =
+                |field: &str, e: &dyn fmt::Display| error(format!("invalid {}: {}", field, e));
+
+            let cue = Cue {
+                index: match row[0].as_str() {
+                    "" => None,
+                    index => Some(index.parse().map_err(|e| parse_error("index", &e))?),
+                },
+                timing: row[2].parse().map_err(|e| parse_error("timing", &e))?,
+                sound_name: row[3].clone(),
+                volume: row[4].parse().map_err(|e| parse_error("volume", &e))?,
+                target_bone: row[5].clone(),
+            };
+
+            let anm_name = &row[1];
+            match timeline.iter_mut().find(|t| &t.anm_name == anm_name) {
+                Some(animation) => animation.cues.push(cue),
+                None => timeline.push(AnimationTimeline {
+                    anm_name: anm_name.clone(),
+                    cues: vec![cue],
+                }),
+            }
+        }
+
+        self.import_timeline(&timeline)
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Parses CSV records, returning each one with the line number it starts at.
+fn parse_csv(data: &str) -> Result<Vec<(usize, Vec<String>)>, TimelineError> {
+    let mut records = Vec::new();
+
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    field.push(chars.next().unwrap());
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push((record_line, std::mem::take(&mut record)));
+                }
+
+                record.clear();
+                line += 1;
+                record_line = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+
+    if quoted {
+        return Err(TimelineError::Csv {
+            line: record_line,
+            message: String::from("unterminated quoted field"),
+        });
+    }
+
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+
+    Ok(records)
+}
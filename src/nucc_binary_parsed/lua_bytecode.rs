@@ -0,0 +1,922 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+
+pub const SIGNATURE: &[u8] = b"\x1bLua";
+pub(crate) const VERSION_5_1: u8 = 0x51;
+
+/// Operands of RK instructions with this bit set refer to constants instead of registers
+const BIT_RK: u32 = 1 << 8;
+const MAX_SBX: i32 = (1 << 17) - 1;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u8,
+    pub format: u8,
+    pub little_endian: bool,
+    pub int_size: u8,
+    pub size_t_size: u8,
+    pub instruction_size: u8,
+    pub number_size: u8,
+    pub integral_numbers: bool,
+}
+
+/// A Lua string, kept as raw bytes since scripts are not always UTF-8.
+/// Serialized as a plain string when possible, or as `{"bytes": "<hex>"}` otherwise.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "LuaStringRepr", into = "LuaStringRepr")]
+pub struct LuaString(pub Vec<u8>);
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum LuaStringRepr {
+    Text(String),
+    Bytes {
+        #[serde(with = "hex::serde")]
+        bytes: Vec<u8>,
+    },
+}
+
+impl From<LuaStringRepr> for LuaString {
+    fn from(repr: LuaStringRepr) -> Self {
+        match repr {
+            LuaStringRepr::Text(text) => LuaString(text.into_bytes()),
+            LuaStringRepr::Bytes { bytes } => LuaString(bytes),
+        }
+    }
+}
+
+impl From<LuaString> for LuaStringRepr {
+    fn from(string: LuaString) -> Self {
+        match String::from_utf8(string.0) {
+            Ok(text) => LuaStringRepr::Text(text),
+            Err(e) => LuaStringRepr::Bytes {
+                bytes: e.into_bytes(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for LuaString {
+    /// Quoted and escaped, as in the disassembly listing
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"")?;
+        for byte in self.0.iter() {
+            match byte {
+                b'"' => f.write_str("\\\"")?,
+                b'\\' => f.write_str("\\\\")?,
+                b'\n' => f.write_str("\\n")?,
+                b'\r' => f.write_str("\\r")?,
+                b'\t' => f.write_str("\\t")?,
+                0x20..=0x7E => write!(f, "{}", *byte as char)?,
+                _ => write!(f, "\\x{:02X}", byte)?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Constant {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+
+    /// Used instead of `Number` by chunks compiled with integral numbers
+    Integer(i64),
+    String(LuaString),
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Nil => f.write_str("nil"),
+            Constant::Boolean(value) => write!(f, "{}", value),
+            Constant::Number(value) => write!(f, "{}", value),
+            Constant::Integer(value) => write!(f, "{}", value),
+            Constant::String(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, Display, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+#[strum(serialize_all = "UPPERCASE")]
+pub enum Opcode {
+    Move,
+    LoadK,
+    LoadBool,
+    LoadNil,
+    GetUpval,
+    GetGlobal,
+    GetTable,
+    SetGlobal,
+    SetUpval,
+    SetTable,
+    NewTable,
+    #[serde(rename = "SELF")]
+    #[strum(serialize = "SELF")]
+    Self_,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Unm,
+    Not,
+    Len,
+    Concat,
+    Jmp,
+    Eq,
+    Lt,
+    Le,
+    Test,
+    TestSet,
+    Call,
+    TailCall,
+    Return,
+    ForLoop,
+    ForPrep,
+    TForLoop,
+    SetList,
+    Close,
+    Closure,
+    VarArg,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpMode {
+    Abc,
+    ABx,
+    AsBx,
+}
+
+impl Opcode {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Opcode::iter().nth(value as usize)
+    }
+
+    pub fn mode(&self) -> OpMode {
+        match self {
+            Opcode::LoadK | Opcode::GetGlobal | Opcode::SetGlobal | Opcode::Closure => OpMode::ABx,
+            Opcode::Jmp | Opcode::ForLoop | Opcode::ForPrep => OpMode::AsBx,
+            _ => OpMode::Abc,
+        }
+    }
+
+    /// Whether the B and C operands can refer to constants
+    fn uses_rk(&self) -> (bool, bool) {
+        match self {
+            Opcode::GetTable | Opcode::Self_ => (false, true),
+            Opcode::SetTable
+            | Opcode::Add
+            | Opcode::Sub
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Mod
+            | Opcode::Pow
+            | Opcode::Eq
+            | Opcode::Lt
+            | Opcode::Le => (true, true),
+            _ => (false, false),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Instruction {
+    pub op: Opcode,
+    pub a: u32,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub b: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub c: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bx: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sbx: Option<i32>,
+}
+
+impl Instruction {
+    pub fn decode(value: u32) -> Option<Self> {
+        let op = Opcode::from_u8((value & 0x3F) as u8)?;
+        let a = (value >> 6) & 0xFF;
+
+        let mut instruction = Self {
+            op,
+            a,
+            b: None,
+            c: None,
+            bx: None,
+            sbx: None,
+        };
+
+        match op.mode() {
+            OpMode::Abc => {
+                instruction.b = Some(value >> 23);
+                instruction.c = Some((value >> 14) & 0x1FF);
+            }
+            OpMode::ABx => instruction.bx = Some(value >> 14),
+            OpMode::AsBx => instruction.sbx = Some((value >> 14) as i32 - MAX_SBX),
+        }
+
+        Some(instruction)
+    }
+
+    /// Returns `None` if an operand does not fit in its field.
+    pub fn encode(&self) -> Option<u32> {
+        let check = |value: u32, bits: u32| (value < 1 << bits).then_some(value);
+
+        let operands = match self.op.mode() {
+            OpMode::Abc => {
+                check(self.b.unwrap_or(0), 9)? << 23 | check(self.c.unwrap_or(0), 9)? << 14
+            }
+            OpMode::ABx => check(self.bx.unwrap_or(0), 18)? << 14,
+            OpMode::AsBx => {
+                let sbx = self.sbx.unwrap_or(0).checked_add(MAX_SBX)?;
+                check(u32::try_from(sbx).ok()?, 18)? << 14
+            }
+        };
+
+        Some(self.op as u32 | check(self.a, 8)? << 6 | operands)
+    }
+}
+
+/// A word of a function's code. `SETLIST` with a C operand of 0 is followed by a word holding
+/// the real C operand, which is kept as raw data instead of being decoded as an instruction.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Word {
+    Instruction(Instruction),
+    Data { data: u32 },
+}
+
+impl Word {
+    /// Whether the next word is raw data
+    fn is_followed_by_data(&self) -> bool {
+        matches!(self, Word::Instruction(instruction) if instruction.op == Opcode::SetList && instruction.c == Some(0))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Local {
+    pub name: LuaString,
+    pub start_pc: i64,
+    pub end_pc: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Function {
+    pub source: Option<LuaString>,
+    pub line_defined: i64,
+    pub last_line_defined: i64,
+    pub upvalue_count: u8,
+    pub param_count: u8,
+    pub is_vararg: u8,
+    pub max_stack_size: u8,
+    pub instructions: Vec<Word>,
+    pub constants: Vec<Constant>,
+    pub functions: Vec<Function>,
+
+    /// Debug info, which is empty in stripped chunks
+    pub line_info: Vec<i64>,
+    pub locals: Vec<Local>,
+    pub upvalues: Vec<LuaString>,
+}
+
+impl Function {
+    /// Number of function prototypes, including this one.
+    pub fn prototype_count(&self) -> usize {
+        1 + self
+            .functions
+            .iter()
+            .map(|function| function.prototype_count())
+            .sum::<usize>()
+    }
+
+    pub fn is_stripped(&self) -> bool {
+        self.line_info.is_empty()
+            && self.locals.is_empty()
+            && self.upvalues.is_empty()
+            && self.functions.iter().all(|function| function.is_stripped())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    pub header: Header,
+    pub main: Function,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LuaError {
+    NotBytecode,
+    UnsupportedVersion(u8),
+    UnsupportedLayout(String),
+    Truncated(usize),
+    InvalidOpcode { offset: usize, opcode: u8 },
+    InvalidConstantType { offset: usize, tag: u8 },
+    OutOfRange(String),
+    Json(String),
+}
+
+impl fmt::Display for LuaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LuaError::NotBytecode => {
+                write!(f, "chunk does not start with the Lua bytecode signature")
+            }
+            LuaError::UnsupportedVersion(version) => {
+                write!(f, "unsupported Lua version {:#X}", version)
+            }
+            LuaError::UnsupportedLayout(message) => write!(f, "unsupported layout: {}", message),
+            LuaError::Truncated(offset) => write!(f, "chunk is truncated at offset {:#X}", offset),
+            LuaError::InvalidOpcode { offset, opcode } => {
+                write!(f, "invalid opcode {} at offset {:#X}", opcode, offset)
+            }
+            LuaError::InvalidConstantType { offset, tag } => {
+                write!(f, "invalid constant type {} at offset {:#X}", tag, offset)
+            }
+            LuaError::OutOfRange(message) => f.write_str(message),
+            LuaError::Json(message) => write!(f, "JSON: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for LuaError {}
+
+impl From<serde_json::Error> for LuaError {
+    fn from(error: serde_json::Error) -> Self {
+        LuaError::Json(error.to_string())
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    header: Header,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, count: usize) -> Result<&[u8], LuaError> {
+        let bytes = self
+            .position
+            .checked_add(count)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or(LuaError::Truncated(self.position))?;
+
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, LuaError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn unsigned(&mut self, size: u8) -> Result<u64, LuaError> {
+        let little_endian = self.header.little_endian;
+        let mut bytes = self.bytes(size as usize)?.to_vec();
+        if little_endian {
+            bytes.reverse();
+        }
+
+        Ok(bytes
+            .iter()
+            .fold(0u64, |value, byte| value << 8 | *byte as u64))
+    }
+
+    /// Reads a value of `size` bytes, sign extended from its top bit
+    fn signed(&mut self, size: u8) -> Result<i64, LuaError> {
+        let value = self.unsigned(size)?;
+
+        let shift = 64 - size as u32 * 8;
+        Ok(((value << shift) as i64) >> shift)
+    }
+
+    fn int(&mut self) -> Result<i64, LuaError> {
+        self.signed(self.header.int_size)
+    }
+
+    fn count(&mut self) -> Result<usize, LuaError> {
+        let offset = self.position;
+        let count = self.int()?;
+
+        // Every element takes at least a byte, so larger counts can only come from corrupted data
+        if count < 0 || count as usize > self.data.len() {
+            return Err(LuaError::Truncated(offset));
+        }
+
+        Ok(count as usize)
+    }
+
+    fn string(&mut self) -> Result<Option<LuaString>, LuaError> {
+        let offset = self.position;
+        let size = usize::try_from(self.unsigned(self.header.size_t_size)?)
+            .map_err(|_| LuaError::Truncated(offset))?;
+        if size == 0 {
+            return Ok(None);
+        }
+
+        // The size includes the terminating null byte
+        let bytes = self.bytes(size)?;
+        Ok(Some(LuaString(bytes[..size - 1].to_vec())))
+    }
+
+    fn number(&mut self) -> Result<Constant, LuaError> {
+        let raw = self.signed(self.header.number_size)?;
+
+        Ok(
+            match (self.header.integral_numbers, self.header.number_size) {
+                (true, _) => Constant::Integer(raw),
+                (false, 4) => Constant::Number(f32::from_bits(raw as u32) as f64),
+                (false, _) => Constant::Number(f64::from_bits(raw as u64)),
+            },
+        )
+    }
+
+    fn function(&mut self) -> Result<Function, LuaError> {
+        let source = self.string()?;
+        let line_defined = self.int()?;
+        let last_line_defined = self.int()?;
+        let upvalue_count = self.u8()?;
+        let param_count = self.u8()?;
+        let is_vararg = self.u8()?;
+        let max_stack_size = self.u8()?;
+
+        let mut instructions: Vec<Word> = Vec::new();
+        for _ in 0..self.count()? {
+            let offset = self.position;
+            let value = self.signed(self.header.instruction_size)? as u32;
+
+            if instructions.last().is_some_and(Word::is_followed_by_data) {
+                instructions.push(Word::Data { data: value });
+                continue;
+            }
+
+            instructions.push(Word::Instruction(Instruction::decode(value).ok_or(
+                LuaError::InvalidOpcode {
+                    offset,
+                    opcode: (value & 0x3F) as u8,
+                },
+            )?));
+        }
+
+        let mut constants = Vec::new();
+        for _ in 0..self.count()? {
+            let offset = self.position;
+            constants.push(match self.u8()? {
+                0 => Constant::Nil,
+                1 => Constant::Boolean(self.u8()? != 0),
+                3 => self.number()?,
+                4 => Constant::String(self.string()?.unwrap_or_default()),
+                tag => return Err(LuaError::InvalidConstantType { offset, tag }),
+            });
+        }
+
+        let mut functions = Vec::new();
+        for _ in 0..self.count()? {
+            functions.push(self.function()?);
+        }
+
+        let mut line_info = Vec::new();
+        for _ in 0..self.count()? {
+            line_info.push(self.int()?);
+        }
+
+        let mut locals = Vec::new();
+        for _ in 0..self.count()? {
+            locals.push(Local {
+                name: self.string()?.unwrap_or_default(),
+                start_pc: self.int()?,
+                end_pc: self.int()?,
+            });
+        }
+
+        let mut upvalues = Vec::new();
+        for _ in 0..self.count()? {
+            upvalues.push(self.string()?.unwrap_or_default());
+        }
+
+        Ok(Function {
+            source,
+            line_defined,
+            last_line_defined,
+            upvalue_count,
+            param_count,
+            is_vararg,
+            max_stack_size,
+            instructions,
+            constants,
+            functions,
+            line_info,
+            locals,
+            upvalues,
+        })
+    }
+}
+
+struct Writer {
+    output: Vec<u8>,
+    header: Header,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.output.push(value);
+    }
+
+    /// Writes the low `size` bytes of `value`
+    fn raw(&mut self, value: u64, size: u8) {
+        let mut bytes = value.to_be_bytes()[8 - size as usize..].to_vec();
+        if self.header.little_endian {
+            bytes.reverse();
+        }
+
+        self.output.extend(bytes);
+    }
+
+    fn int(&mut self, value: i64, path: &str) -> Result<(), LuaError> {
+        let size = self.header.int_size;
+        if size == 4 && i32::try_from(value).is_err() {
+            return Err(LuaError::OutOfRange(format!(
+                "{}: {} does not fit in a {} byte int",
+                path, value, size
+            )));
+        }
+
+        self.raw(value as u64, size);
+        Ok(())
+    }
+
+    fn count(&mut self, count: usize, path: &str) -> Result<(), LuaError> {
+        self.int(count as i64, path)
+    }
+
+    fn string(&mut self, string: Option<&LuaString>) {
+        match string {
+            Some(string) => {
+                self.raw(string.0.len() as u64 + 1, self.header.size_t_size);
+                self.output.extend_from_slice(&string.0);
+                self.output.push(0);
+            }
+            None => self.raw(0, self.header.size_t_size),
+        }
+    }
+
+    fn number(&mut self, constant: &Constant, path: &str) -> Result<(), LuaError> {
+        let size = self.header.number_size;
+
+        let raw = if self.header.integral_numbers {
+            let value = match constant {
+                Constant::Integer(value) => Some(*value),
+                Constant::Number(value) if value.fract() == 0.0 => Some(*value as i64),
+                _ => None,
+            };
+
+            value.filter(|value| size == 8 || i32::try_from(*value).is_ok())
+        } else {
+            match constant {
+                Constant::Integer(value) => Some(float_bits(*value as f64, size)),
+                Constant::Number(value) => Some(float_bits(*value, size)),
+                _ => None,
+            }
+        };
+
+        let raw = raw.ok_or_else(|| {
+            LuaError::OutOfRange(format!(
+                "{}: {} cannot be stored as a {} byte integral number",
+                path, constant, size
+            ))
+        })?;
+
+        self.raw(raw as u64, size);
+        Ok(())
+    }
+
+    fn function(&mut self, function: &Function, path: &str) -> Result<(), LuaError> {
+        self.string(function.source.as_ref());
+        self.int(function.line_defined, &format!("{}.line_defined", path))?;
+        self.int(
+            function.last_line_defined,
+            &format!("{}.last_line_defined", path),
+        )?;
+        self.u8(function.upvalue_count);
+        self.u8(function.param_count);
+        self.u8(function.is_vararg);
+        self.u8(function.max_stack_size);
+
+        self.count(function.instructions.len(), path)?;
+        for (pc, word) in function.instructions.iter().enumerate() {
+            let value = match word {
+                Word::Instruction(instruction) => instruction.encode().ok_or_else(|| {
+                    LuaError::OutOfRange(format!(
+                        "{}.instructions[{}]: operands of {} do not fit in the instruction",
+                        path, pc, instruction.op
+                    ))
+                })?,
+                Word::Data { data } => *data,
+            };
+
+            self.raw(value as u64, self.header.instruction_size);
+        }
+
+        self.count(function.constants.len(), path)?;
+        for (i, constant) in function.constants.iter().enumerate() {
+            match constant {
+                Constant::Nil => self.u8(0),
+                Constant::Boolean(value) => {
+                    self.u8(1);
+                    self.u8(*value as u8);
+                }
+                Constant::Number(_) | Constant::Integer(_) => {
+                    self.u8(3);
+                    self.number(constant, &format!("{}.constants[{}]", path, i))?;
+                }
+                Constant::String(value) => {
+                    self.u8(4);
+                    self.string(Some(value));
+                }
+            }
+        }
+
+        self.count(function.functions.len(), path)?;
+        for (i, child) in function.functions.iter().enumerate() {
+            self.function(child, &format!("{}.functions[{}]", path, i))?;
+        }
+
+        self.count(function.line_info.len(), path)?;
+        for (pc, line) in function.line_info.iter().enumerate() {
+            self.int(*line, &format!("{}.line_info[{}]", path, pc))?;
+        }
+
+        self.count(function.locals.len(), path)?;
+        for (i, local) in function.locals.iter().enumerate() {
+            self.string(Some(&local.name));
+            self.int(local.start_pc, &format!("{}.locals[{}].start_pc", path, i))?;
+            self.int(local.end_pc, &format!("{}.locals[{}].end_pc", path, i))?;
+        }
+
+        self.count(function.upvalues.len(), path)?;
+        for upvalue in function.upvalues.iter() {
+            self.string(Some(upvalue));
+        }
+
+        Ok(())
+    }
+}
+
+fn float_bits(value: f64, size: u8) -> i64 {
+    match size {
+        4 => (value as f32).to_bits() as i64,
+        _ => value.to_bits() as i64,
+    }
+}
+
+impl Header {
+    pub const SIZE: usize = 12;
+
+    pub fn read(data: &[u8]) -> Result<Self, LuaError> {
+        if !data.starts_with(SIGNATURE) {
+            return Err(LuaError::NotBytecode);
+        }
+
+        let bytes = data
+            .get(..Self::SIZE)
+            .ok_or(LuaError::Truncated(data.len()))?;
+
+        let header = Self {
+            version: bytes[4],
+            format: bytes[5],
+            little_endian: bytes[6] != 0,
+            int_size: bytes[7],
+            size_t_size: bytes[8],
+            instruction_size: bytes[9],
+            number_size: bytes[10],
+            integral_numbers: bytes[11] != 0,
+        };
+
+        header.check()?;
+        Ok(header)
+    }
+
+    fn check(&self) -> Result<(), LuaError> {
+        if self.version != VERSION_5_1 {
+            return Err(LuaError::UnsupportedVersion(self.version));
+        }
+
+        for (name, size) in [
+            ("int", self.int_size),
+            ("size_t", self.size_t_size),
+            ("lua_Number", self.number_size),
+        ] {
+            if !matches!(size, 4 | 8) {
+                return Err(LuaError::UnsupportedLayout(format!(
+                    "{} is {} bytes",
+                    name, size
+                )));
+            }
+        }
+
+        if self.instruction_size != 4 {
+            return Err(LuaError::UnsupportedLayout(format!(
+                "instructions are {} bytes",
+                self.instruction_size
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(SIGNATURE);
+        output.extend([
+            self.version,
+            self.format,
+            self.little_endian as u8,
+            self.int_size,
+            self.size_t_size,
+            self.instruction_size,
+            self.number_size,
+            self.integral_numbers as u8,
+        ]);
+    }
+}
+
+impl Chunk {
+    pub fn read(data: &[u8]) -> Result<Self, LuaError> {
+        let header = Header::read(data)?;
+        let mut reader = Reader {
+            data,
+            position: Header::SIZE,
+            header: header.clone(),
+        };
+
+        let main = reader.function()?;
+        Ok(Self { header, main })
+    }
+
+    /// Assembles the chunk back into bytecode, using the layout described by its header.
+    pub fn write(&self) -> Result<Vec<u8>, LuaError> {
+        self.header.check()?;
+
+        let mut writer = Writer {
+            output: Vec::new(),
+            header: self.header.clone(),
+        };
+
+        self.header.write(&mut writer.output);
+        writer.function(&self.main, "main")?;
+        Ok(writer.output)
+    }
+
+    /// Listing of every function prototype, in the style of `luac -l -l`.
+    pub fn disassemble(&self) -> String {
+        let mut output = String::new();
+        write_function(&mut output, &self.main, "main");
+        output
+    }
+}
+
+fn write_function(output: &mut String, function: &Function, name: &str) {
+    use std::fmt::Write;
+
+    let source = function
+        .source
+        .as_ref()
+        .map(|source| String::from_utf8_lossy(&source.0).to_string())
+        .unwrap_or_else(|| String::from("?"));
+
+    let _ = writeln!(
+        output,
+        "{} <{}:{},{}> ({} instructions)",
+        name,
+        source,
+        function.line_defined,
+        function.last_line_defined,
+        function.instructions.len()
+    );
+
+    let _ = writeln!(
+        output,
+        "{}{} params, {} slots, {} upvalues, {} locals, {} constants, {} functions",
+        function.param_count,
+        if function.is_vararg != 0 { "+" } else { "" },
+        function.max_stack_size,
+        function.upvalue_count,
+        function.locals.len(),
+        function.constants.len(),
+        function.functions.len()
+    );
+
+    let constant = |index: u32| {
+        function
+            .constants
+            .get(index as usize)
+            .map(|constant| constant.to_string())
+            .unwrap_or_else(|| String::from("?"))
+    };
+
+    // Constants are shown as negative numbers starting from -1, like luac does
+    let rk = |value: u32, uses_rk: bool| {
+        if uses_rk && value & BIT_RK != 0 {
+            -1 - (value & !BIT_RK) as i64
+        } else {
+            value as i64
+        }
+    };
+
+    for (pc, word) in function.instructions.iter().enumerate() {
+        let line = function
+            .line_info
+            .get(pc)
+            .map(|line| line.to_string())
+            .unwrap_or_else(|| String::from("-"));
+
+        let instruction = match word {
+            Word::Instruction(instruction) => instruction,
+            Word::Data { data } => {
+                let _ = writeln!(output, "\t{}\t[{}]\t{:<9}\t{}", pc + 1, line, "DATA", data);
+                continue;
+            }
+        };
+
+        let (b_rk, c_rk) = instruction.op.uses_rk();
+        let mut comments = Vec::new();
+
+        let operands = match instruction.op.mode() {
+            OpMode::Abc => {
+                let (b, c) = (instruction.b.unwrap_or(0), instruction.c.unwrap_or(0));
+                for (value, uses_rk) in [(b, b_rk), (c, c_rk)] {
+                    if uses_rk && value & BIT_RK != 0 {
+                        comments.push(constant(value & !BIT_RK));
+                    }
+                }
+
+                format!("{} {} {}", instruction.a, rk(b, b_rk), rk(c, c_rk))
+            }
+            OpMode::ABx => {
+                let bx = instruction.bx.unwrap_or(0);
+                if instruction.op == Opcode::Closure {
+                    comments.push(format!("function {}", bx));
+                    format!("{} {}", instruction.a, bx)
+                } else {
+                    comments.push(constant(bx));
+                    format!("{} {}", instruction.a, -1 - bx as i64)
+                }
+            }
+            OpMode::AsBx => {
+                let sbx = instruction.sbx.unwrap_or(0);
+                comments.push(format!("to {}", pc as i64 + 2 + sbx as i64));
+                format!("{} {}", instruction.a, sbx)
+            }
+        };
+
+        let _ = write!(
+            output,
+            "\t{}\t[{}]\t{:<9}\t{}",
+            pc + 1,
+            line,
+            instruction.op.to_string(),
+            operands
+        );
+
+        if !comments.is_empty() {
+            let _ = write!(output, "\t; {}", comments.join(" "));
+        }
+
+        output.push('\n');
+    }
+
+    let _ = writeln!(output, "constants ({})", function.constants.len());
+    for (i, constant) in function.constants.iter().enumerate() {
+        let _ = writeln!(output, "\t{}\t{}", i + 1, constant);
+    }
+
+    let _ = writeln!(output, "locals ({})", function.locals.len());
+    for (i, local) in function.locals.iter().enumerate() {
+        let _ = writeln!(
+            output,
+            "\t{}\t{}\t{}\t{}",
+            i,
+            String::from_utf8_lossy(&local.name.0),
+            local.start_pc + 1,
+            local.end_pc + 1
+        );
+    }
+
+    let _ = writeln!(output, "upvalues ({})", function.upvalues.len());
+    for (i, upvalue) in function.upvalues.iter().enumerate() {
+        let _ = writeln!(output, "\t{}\t{}", i, String::from_utf8_lossy(&upvalue.0));
+    }
+
+    for (i, child) in function.functions.iter().enumerate() {
+        output.push('\n');
+        write_function(output, child, &format!("{}.function[{}]", name, i));
+    }
+}
@@ -1,13 +1,46 @@
+use super::lua_bytecode::{Chunk, LuaError, SIGNATURE, VERSION_5_1};
 use super::NuccBinaryParsed;
 use super::NuccBinaryType;
+use crate::validation::Diagnostic;
 
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct LuaFile {
     pub file: Vec<u8>,
 }
 
+impl LuaFile {
+    /// Whether the file is compiled Lua 5.1 bytecode rather than source code.
+    pub fn is_bytecode(&self) -> bool {
+        self.file.starts_with(SIGNATURE) && self.file.get(SIGNATURE.len()) == Some(&VERSION_5_1)
+    }
+
+    pub fn chunk(&self) -> Result<Chunk, LuaError> {
+        Chunk::read(&self.file)
+    }
+
+    pub fn disassemble(&self) -> Result<String, LuaError> {
+        Ok(self.chunk()?.disassemble())
+    }
+
+    pub fn disassembly_json(&self) -> Result<String, LuaError> {
+        Ok(serde_json::to_string_pretty(&self.chunk()?).unwrap())
+    }
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self, LuaError> {
+        Ok(Self {
+            file: chunk.write()?,
+        })
+    }
+
+    /// Rebuilds the bytecode from the output of `disassembly_json`, keeping the layout of its header.
+    pub fn assemble(disassembly_json: &[u8]) -> Result<Self, LuaError> {
+        Self::from_chunk(&serde_json::from_slice(disassembly_json)?)
+    }
+}
+
 impl NuccBinaryParsed for LuaFile {
     fn binary_type(&self) -> NuccBinaryType {
         NuccBinaryType::LUA
@@ -29,6 +62,40 @@ impl NuccBinaryParsed for LuaFile {
             file: data.to_vec(),
         }
     }
+
+    fn info(&self) -> serde_json::Value {
+        if !self.is_bytecode() {
+            return json!({
+                "type": self.binary_type().to_string(),
+                "size": self.file.len(),
+                "kind": "source",
+            });
+        }
+
+        match self.chunk() {
+            Ok(chunk) => json!({
+                "type": self.binary_type().to_string(),
+                "size": self.file.len(),
+                "kind": "bytecode",
+                "header": chunk.header,
+                "stripped": chunk.main.is_stripped(),
+                "functions": chunk.main.prototype_count(),
+            }),
+            Err(e) => json!({
+                "type": self.binary_type().to_string(),
+                "size": self.file.len(),
+                "kind": "bytecode",
+                "error": e.to_string(),
+            }),
+        }
+    }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        match self.is_bytecode().then(|| self.chunk()) {
+            Some(Err(e)) => vec![Diagnostic::error(String::from("file"), e.to_string())],
+            _ => Vec::new(),
+        }
+    }
 }
 
 impl From<&[u8]> for LuaFile {
@@ -1,6 +1,8 @@
 use super::endian_from_bool;
+use super::message_markup::{self, MarkupMismatch, MarkupToken};
 use super::NuccBinaryParsed;
 use super::NuccBinaryType;
+use crate::validation::{find_duplicates, Diagnostic};
 
 use binary_stream::SeekStream;
 use binary_stream::{BinaryReader, BinaryWriter, MemoryStream, SliceStream};
@@ -10,7 +12,7 @@ use deku::ctx::Endian;
 use deku::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[deku_derive(DekuRead, DekuWrite)]
 #[deku(
     endian = "endian",
@@ -41,6 +43,16 @@ pub struct Entry {
     pub string: String,
 }
 
+impl Entry {
+    pub fn markup(&self) -> Vec<MarkupToken> {
+        message_markup::tokenize_markup(&self.string)
+    }
+
+    pub fn plain_text(&self) -> String {
+        message_markup::plain_text(&self.markup())
+    }
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct MessageInfo {
     pub unk0: u32,
@@ -69,6 +81,53 @@ impl NuccBinaryParsed for MessageInfo {
     {
         serde_json::from_slice(data).unwrap()
     }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            if entry.msg_id_crc32.len() != 4 {
+                diagnostics.push(Diagnostic::error(
+                    format!("entries[{}].msg_id_crc32", i),
+                    format!("expected 4 bytes, found {}", entry.msg_id_crc32.len()),
+                ));
+            }
+        }
+
+        for (i, first) in find_duplicates(self.entries.iter().map(|e| &e.msg_id_crc32)) {
+            diagnostics.push(Diagnostic::warning(
+                format!("entries[{}].msg_id_crc32", i),
+                format!("duplicate message ID, first used by entries[{}]", first),
+            ));
+        }
+
+        diagnostics
+    }
+}
+
+impl MessageInfo {
+    /// Compares the control tokens of each translated entry against the entry with the same ID in `self`.
+    /// Returns the hex ID of each mismatching entry along with the mismatch.
+    pub fn compare_markup(&self, translated: &MessageInfo) -> Vec<(String, MarkupMismatch)> {
+        let mut mismatches = Vec::new();
+
+        for entry in translated.entries.iter() {
+            if let Some(source) = self
+                .entries
+                .iter()
+                .find(|e| e.msg_id_crc32 == entry.msg_id_crc32)
+            {
+                let msg_id = hex::encode(&entry.msg_id_crc32);
+                mismatches.extend(
+                    message_markup::compare_markup(&source.string, &entry.string)
+                        .into_iter()
+                        .map(|mismatch| (msg_id.clone(), mismatch)),
+                );
+            }
+        }
+
+        mismatches
+    }
 }
 
 impl From<(&[u8], Endian)> for MessageInfo {
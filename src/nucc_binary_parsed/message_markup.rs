@@ -0,0 +1,275 @@
+use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MarkupToken {
+    Text(String),
+    LineBreak(String),
+    Tag {
+        raw: String,
+        name: String,
+        value: Option<String>,
+        closing: bool,
+    },
+    Placeholder(String),
+}
+
+impl MarkupToken {
+    pub fn is_control(&self) -> bool {
+        !matches!(self, MarkupToken::Text(_))
+    }
+
+    /// Key used when comparing control tokens between two strings.
+    /// Both line break forms are treated as the same token.
+    fn control_key(&self) -> Option<String> {
+        match self {
+            MarkupToken::Text(_) => None,
+            MarkupToken::LineBreak(_) => Some(String::from("\\n")),
+            MarkupToken::Tag { raw, .. } => Some(raw.clone()),
+            MarkupToken::Placeholder(raw) => Some(raw.clone()),
+        }
+    }
+}
+
+impl fmt::Display for MarkupToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkupToken::Text(raw)
+            | MarkupToken::LineBreak(raw)
+            | MarkupToken::Tag { raw, .. }
+            | MarkupToken::Placeholder(raw) => f.write_str(raw),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MarkupMismatch {
+    Missing(String),
+    Unexpected(String),
+    UnclosedTag(String),
+    UnmatchedClosingTag(String),
+}
+
+impl fmt::Display for MarkupMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkupMismatch::Missing(token) => write!(f, "missing control token `{}`", token),
+            MarkupMismatch::Unexpected(token) => {
+                write!(f, "unexpected control token `{}`", token)
+            }
+            MarkupMismatch::UnclosedTag(name) => write!(f, "tag `{}` is never closed", name),
+            MarkupMismatch::UnmatchedClosingTag(name) => {
+                write!(f, "closing tag `{}` has no opening tag", name)
+            }
+        }
+    }
+}
+
+/// Splits a message string into text and control tokens.
+/// Concatenating the tokens gives back the original string.
+pub fn tokenize_markup(string: &str) -> Vec<MarkupToken> {
+    fn flush(tokens: &mut Vec<MarkupToken>, text: &mut String) {
+        if !text.is_empty() {
+            tokens.push(MarkupToken::Text(std::mem::take(text)));
+        }
+    }
+
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+
+    let mut rest = string;
+    while let Some(c) = rest.chars().next() {
+        let token = match c {
+            '\n' => Some((MarkupToken::LineBreak(String::from("\n")), 1)),
+            '\r' if rest.starts_with("\r\n") => {
+                Some((MarkupToken::LineBreak(String::from("\r\n")), 2))
+            }
+            '\\' if rest.starts_with("\\n") => {
+                Some((MarkupToken::LineBreak(String::from("\\n")), 2))
+            }
+            '<' => parse_tag(rest),
+            '%' => parse_placeholder(rest),
+            _ => None,
+        };
+
+        match token {
+            Some((token, len)) => {
+                flush(&mut tokens, &mut text);
+                tokens.push(token);
+                rest = &rest[len..];
+            }
+            None => {
+                // Escaped percent signs stay in the text as-is
+                let len = if rest.starts_with("%%") {
+                    2
+                } else {
+                    c.len_utf8()
+                };
+                text.push_str(&rest[..len]);
+                rest = &rest[len..];
+            }
+        }
+    }
+
+    flush(&mut tokens, &mut text);
+    tokens
+}
+
+fn parse_tag(input: &str) -> Option<(MarkupToken, usize)> {
+    let end = input.find('>')?;
+    let inner = &input[1..end];
+
+    if inner.contains('<') || inner.contains('\n') {
+        return None;
+    }
+
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(inner) => (true, inner),
+        None => (false, inner),
+    };
+
+    let split = inner
+        .find(|c: char| c == '=' || c.is_whitespace())
+        .unwrap_or(inner.len());
+    let name = &inner[..split];
+
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return None;
+    }
+
+    let value = inner[split..]
+        .trim_start_matches(|c: char| c == '=' || c.is_whitespace())
+        .trim_end();
+
+    Some((
+        MarkupToken::Tag {
+            raw: input[..=end].to_string(),
+            name: name.to_string(),
+            value: (!value.is_empty()).then(|| value.to_string()),
+            closing,
+        },
+        end + 1,
+    ))
+}
+
+fn parse_placeholder(input: &str) -> Option<(MarkupToken, usize)> {
+    let bytes = input.as_bytes();
+    let mut pos = 1;
+
+    let skip_digits = |pos: &mut usize| {
+        while *pos < bytes.len() && bytes[*pos].is_ascii_digit() {
+            *pos += 1;
+        }
+    };
+
+    // Positional argument, e.g. %1$s
+    skip_digits(&mut pos);
+    if pos > 1 {
+        if bytes.get(pos) == Some(&b'$') {
+            pos += 1;
+        } else {
+            pos = 1;
+        }
+    }
+
+    while pos < bytes.len() && b"-+ #0".contains(&bytes[pos]) {
+        pos += 1;
+    }
+
+    skip_digits(&mut pos);
+    if bytes.get(pos) == Some(&b'.') {
+        pos += 1;
+        skip_digits(&mut pos);
+    }
+
+    match bytes.get(pos) {
+        Some(b'c' | b'd' | b'i' | b'u' | b'x' | b'X' | b'f' | b'F' | b'g' | b's' | b'S') => {
+            Some((MarkupToken::Placeholder(input[..=pos].to_string()), pos + 1))
+        }
+        _ => None,
+    }
+}
+
+/// Renders the tokens as plain text, dropping tags and keeping placeholders.
+pub fn plain_text(tokens: &[MarkupToken]) -> String {
+    tokens
+        .iter()
+        .filter_map(|token| match token {
+            MarkupToken::Text(text) => Some(text.replace("%%", "%")),
+            MarkupToken::LineBreak(_) => Some(String::from("\n")),
+            MarkupToken::Tag { .. } => None,
+            MarkupToken::Placeholder(raw) => Some(raw.clone()),
+        })
+        .collect()
+}
+
+/// Checks that `translated` keeps every control token of `source`, and that its tags are balanced.
+pub fn compare_markup(source: &str, translated: &str) -> Vec<MarkupMismatch> {
+    fn count_controls(tokens: &[MarkupToken]) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for key in tokens.iter().filter_map(MarkupToken::control_key) {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    let source_tokens = tokenize_markup(source);
+    let translated_tokens = tokenize_markup(translated);
+
+    let mut mismatches = Vec::new();
+
+    // Walk the tokens in order so the mismatches are reported in a stable order
+    let mut available = count_controls(&translated_tokens);
+    for key in source_tokens.iter().filter_map(MarkupToken::control_key) {
+        match available.get_mut(&key) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => mismatches.push(MarkupMismatch::Missing(key)),
+        }
+    }
+
+    let mut expected = count_controls(&source_tokens);
+    for key in translated_tokens
+        .iter()
+        .filter_map(MarkupToken::control_key)
+    {
+        match expected.get_mut(&key) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => mismatches.push(MarkupMismatch::Unexpected(key)),
+        }
+    }
+
+    let mut open_tags: Vec<&str> = Vec::new();
+    for token in translated_tokens.iter() {
+        if let MarkupToken::Tag { name, closing, .. } = token {
+            if !closing {
+                open_tags.push(name);
+            } else if let Some(pos) = open_tags.iter().rposition(|open| open == name) {
+                open_tags.remove(pos);
+            } else if source_tokens
+                .iter()
+                .any(|t| matches!(t, MarkupToken::Tag { name: n, closing: false, .. } if n == name))
+            {
+                mismatches.push(MarkupMismatch::UnmatchedClosingTag(name.clone()));
+            }
+        }
+    }
+
+    // Only report unclosed tags that are closed somewhere in the source,
+    // since some tags (e.g. button glyphs) never have a closing tag
+    for name in open_tags {
+        if source_tokens
+            .iter()
+            .any(|t| matches!(t, MarkupToken::Tag { name: n, closing: true, .. } if n == name))
+        {
+            mismatches.push(MarkupMismatch::UnclosedTag(name.to_string()));
+        }
+    }
+
+    mismatches
+}
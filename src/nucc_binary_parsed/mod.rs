@@ -1,13 +1,20 @@
 mod characode;
+mod dds_codec;
 mod dds_file;
+mod ev_cipher;
 mod ev_file;
+mod ev_timeline;
 mod fcv_file;
+mod lua_bytecode;
 mod lua_file;
 mod message_info;
+mod message_markup;
 mod player_color_param;
+mod player_color_swatch;
 mod png_file;
 mod prm_load;
 mod sound_test_param;
+mod spm;
 mod stage_info;
 mod xml_file;
 
@@ -16,25 +23,43 @@ use crc::{Crc, CRC_32_BZIP2};
 use deku::{
     bitvec::{BitVec, BitView},
     ctx::Endian,
+    error::DekuError,
     DekuRead, DekuUpdate, DekuWrite,
 };
 use downcast_rs::{impl_downcast, Downcast};
 use strum::IntoEnumIterator;
 
+use super::Diagnostic;
 use super::NuccBinaryType;
+use super::{unwrap_binary_chunk, wrap_binary_chunk, XfbinError};
 
-pub use characode::CharaCode;
-pub use dds_file::DdsFile;
-pub use ev_file::{EvFile, Version as EvVersion};
+pub use characode::{CharaCode, CharaCodeError, CharaCodeMap, Entry as CharaCodeEntry};
+pub use dds_file::{DdsDx10Header, DdsError, DdsFile, DdsFormat, DdsHeader, DdsPixelFormat};
+pub use ev_cipher::{Cipher as EvCipher, MAX_KEY_LEN as EV_MAX_KEY_LEN};
+pub use ev_file::{EvFile, Version as EvVersion, VersionDetection as EvVersionDetection};
+pub use ev_timeline::{
+    AnimationTimeline as EvAnimationTimeline, Cue as EvCue, TimelineError as EvTimelineError,
+};
 pub use fcv_file::FcvFile;
+pub use lua_bytecode::{
+    Chunk as LuaChunk, Constant as LuaConstant, Function as LuaFunction, Header as LuaHeader,
+    Instruction as LuaInstruction, Local as LuaLocal, LuaError, LuaString, OpMode as LuaOpMode,
+    Opcode as LuaOpcode, Word as LuaWord,
+};
 pub use lua_file::LuaFile;
-pub use message_info::MessageInfo;
-pub use player_color_param::PlayerColorParam;
-pub use png_file::PngFile;
-pub use prm_load::PrmLoad;
-pub use sound_test_param::SoundTestParam;
+pub use message_info::{Entry as MessageInfoEntry, MessageInfo};
+pub use message_markup::{
+    compare_markup, plain_text, tokenize_markup, MarkupMismatch, MarkupToken,
+};
+pub use player_color_param::{
+    Entry as PlayerColorEntry, PlayerColorError, PlayerColorParam, Rgb as PlayerColorRgb,
+};
+pub use png_file::{PngChunk, PngError, PngFile, PngHeader};
+pub use prm_load::{Entry as PrmLoadEntry, FileType as PrmLoadFileType, PrmLoad, PrmLoadError};
+pub use sound_test_param::{Entry as SoundTestEntry, SoundTestParam};
+pub use spm::{Spm, SpmError, SpmMove, SpmParameter, SpmValue};
 pub use stage_info::StageInfo;
-pub use xml_file::XmlFile;
+pub use xml_file::{XmlAttribute, XmlDocument, XmlElement, XmlError, XmlFile, XmlNode};
 
 pub trait NuccBinaryParsed: Downcast {
     fn binary_type(&self) -> NuccBinaryType;
@@ -43,6 +68,15 @@ pub trait NuccBinaryParsed: Downcast {
     fn deserialize(data: &[u8], use_json: bool) -> Self
     where
         Self: Sized;
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        Vec::new()
+    }
+
+    /// Summary of the file for display, without its full contents.
+    fn info(&self) -> serde_json::Value {
+        serde_json::json!({ "type": self.binary_type().to_string() })
+    }
 }
 
 impl_downcast!(NuccBinaryParsed);
@@ -51,67 +85,86 @@ const UNEXPECTED_ENUM: &str = "Version index out of bounds";
 
 pub struct NuccBinaryParsedReader<'a>(pub NuccBinaryType, pub &'a [u8], pub Endian, pub usize);
 
-impl From<NuccBinaryParsedReader<'_>> for Box<dyn NuccBinaryParsed> {
-    fn from(reader: NuccBinaryParsedReader<'_>) -> Self {
-        let NuccBinaryParsedReader(binary_type, data, endian, version) = reader;
+impl<'a> NuccBinaryParsedReader<'a> {
+    /// Same as constructing the reader directly, but `data` is the size-prefixed data of a `nuccChunkBinary`.
+    /// Returns an error if the prefix, read in `endian`, does not match the size of the payload.
+    pub fn from_chunk_data(
+        binary_type: NuccBinaryType,
+        data: &'a [u8],
+        endian: Endian,
+        version: usize,
+    ) -> Result<Self, XfbinError> {
+        Ok(Self(
+            binary_type,
+            unwrap_binary_chunk(data, endian)?,
+            endian,
+            version,
+        ))
+    }
 
-        match binary_type {
-            NuccBinaryType::CharaCode(_) => {
-                Box::new(CharaCode::read(data.view_bits(), endian).unwrap().1)
-            }
+    /// Same as converting into `Box<dyn NuccBinaryParsed>`, but returns an error instead of panicking
+    /// if a field of a `CharaCode`, `Ev` or `PrmLoad` cannot be read (e.g. a string that is not valid
+    /// UTF-8 in the given Ev version).
+    pub fn try_read(self) -> Result<Box<dyn NuccBinaryParsed>, DekuError> {
+        let NuccBinaryParsedReader(binary_type, data, endian, version) = self;
+
+        Ok(match binary_type {
+            NuccBinaryType::CharaCode(_) => Box::new(CharaCode::read(data.view_bits(), endian)?.1),
             NuccBinaryType::DDS => Box::new(DdsFile::from(data)),
-            NuccBinaryType::Ev(_) => Box::new(
-                EvFile::read(
-                    data.view_bits(),
-                    (
-                        endian,
-                        EvVersion::iter().nth(version).expect(UNEXPECTED_ENUM),
-                    ),
-                )
-                .unwrap()
-                .1,
-            ),
+            NuccBinaryType::Ev(_) => {
+                let version = match EvVersion::iter().nth(version).expect(UNEXPECTED_ENUM) {
+                    EvVersion::Auto => EvFile::detect_version(data, endian).version,
+                    version => version,
+                };
+
+                Box::new(EvFile::read(data.view_bits(), (endian, version))?.1)
+            }
             NuccBinaryType::FCV => Box::new(FcvFile::from(data)),
             NuccBinaryType::LUA => Box::new(LuaFile::from(data)),
             NuccBinaryType::MessageInfo(_) => Box::new(MessageInfo::from((data, endian))),
             NuccBinaryType::PlayerColorParam(_) => Box::new(PlayerColorParam::from((data, endian))),
             NuccBinaryType::PNG => Box::new(PngFile::from(data)),
-            NuccBinaryType::PrmLoad(_) => Box::new(PrmLoad::read_parsed(data, endian)),
+            NuccBinaryType::PrmLoad(_) => Box::new(PrmLoad::read(data.view_bits(), endian)?.1),
             NuccBinaryType::SoundTestParam(_) => Box::new(SoundTestParam::from((data, endian))),
             NuccBinaryType::StageInfo(_) => Box::new(StageInfo::from((data, endian))),
             NuccBinaryType::XML => Box::new(XmlFile::from(data)),
-        }
+        })
+    }
+}
+
+impl From<NuccBinaryParsedReader<'_>> for Box<dyn NuccBinaryParsed> {
+    fn from(reader: NuccBinaryParsedReader<'_>) -> Self {
+        reader.try_read().unwrap()
     }
 }
 
 pub struct NuccBinaryParsedWriter(pub Box<dyn NuccBinaryParsed>, pub usize);
 
-impl From<NuccBinaryParsedWriter> for Vec<u8> {
-    fn from(writer: NuccBinaryParsedWriter) -> Self {
-        let NuccBinaryParsedWriter(boxed, _version) = writer;
+impl NuccBinaryParsedWriter {
+    /// Same as converting into `Vec<u8>`, but returns an error instead of panicking
+    /// if a field cannot be written (e.g. a string that does not fit in its fixed-size field).
+    pub fn try_write(self) -> Result<Vec<u8>, DekuError> {
+        let NuccBinaryParsedWriter(boxed, _version) = self;
 
-        match boxed.binary_type() {
+        Ok(match boxed.binary_type() {
             NuccBinaryType::CharaCode(_) => {
                 let mut chara = *boxed.downcast::<CharaCode>().ok().unwrap();
-                chara.update().unwrap();
+                chara.update()?;
 
                 let mut output = BitVec::new();
-                chara
-                    .write(&mut output, endian_from_bool(chara.big_endian))
-                    .unwrap();
+                chara.write(&mut output, endian_from_bool(chara.big_endian))?;
                 output.into_vec()
             }
             NuccBinaryType::DDS => (*boxed.downcast::<DdsFile>().ok().unwrap()).into(),
             NuccBinaryType::Ev(_) => {
                 let mut ev = *boxed.downcast::<EvFile>().ok().unwrap();
-                ev.update().unwrap();
+                ev.update()?;
 
                 let mut output = BitVec::new();
                 ev.write(
                     &mut output,
                     (endian_from_bool(ev.big_endian), ev.stored_version),
-                )
-                .unwrap();
+                )?;
                 output.into_vec()
             }
             NuccBinaryType::FCV => (*boxed.downcast::<FcvFile>().ok().unwrap()).into(),
@@ -124,14 +177,25 @@ impl From<NuccBinaryParsedWriter> for Vec<u8> {
             }
             NuccBinaryType::PNG => (*boxed.downcast::<PngFile>().ok().unwrap()).into(),
             NuccBinaryType::PrmLoad(_) => {
-                (*boxed.downcast::<PrmLoad>().ok().unwrap()).write_parsed()
+                (*boxed.downcast::<PrmLoad>().ok().unwrap()).try_write_parsed()?
             }
             NuccBinaryType::SoundTestParam(_) => {
                 (*boxed.downcast::<SoundTestParam>().ok().unwrap()).into()
             }
             NuccBinaryType::StageInfo(_) => (*boxed.downcast::<StageInfo>().ok().unwrap()).into(),
             NuccBinaryType::XML => (*boxed.downcast::<XmlFile>().ok().unwrap()).into(),
-        }
+        })
+    }
+
+    /// Writes the payload with the size prefix of a `nuccChunkBinary`, in `endian`.
+    pub fn try_write_chunk_data(self, endian: Endian) -> Result<Vec<u8>, DekuError> {
+        Ok(wrap_binary_chunk(&self.try_write()?, endian))
+    }
+}
+
+impl From<NuccBinaryParsedWriter> for Vec<u8> {
+    fn from(writer: NuccBinaryParsedWriter) -> Self {
+        writer.try_write().unwrap()
     }
 }
 
@@ -173,7 +237,7 @@ impl From<NuccBinaryParsedSerializer> for Vec<u8> {
 
 const MSG_ID_HASH: Crc<u32> = Crc::<u32>::new(&CRC_32_BZIP2);
 
-fn calc_crc32(data: &[u8]) -> Vec<u8> {
+pub(crate) fn calc_crc32(data: &[u8]) -> Vec<u8> {
     let mut output = BitVec::new();
     u32::write(&MSG_ID_HASH.checksum(data), &mut output, Endian::Little).unwrap();
     output.into()
@@ -1,12 +1,90 @@
+use super::characode::CharaCodeMap;
 use super::endian_from_bool;
 use super::NuccBinaryParsed;
 use super::NuccBinaryType;
+use crate::validation::{find_duplicates, Diagnostic};
 
 use binary_stream::{BinaryReader, BinaryWriter, MemoryStream, SeekStream, SliceStream};
 use deku::bitvec::{BitSlice, BitVec, BitView, Msb0};
 use deku::ctx::Endian;
 use deku::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Color channels, stored as the full `u32` values from the file.
+/// Serialized as `#RRGGBB` when every channel fits in a byte, and as an array otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rgb(pub [u32; 3]);
+
+impl Rgb {
+    /// The channels as bytes, or `None` if any of them is above 255
+    pub fn to_u8(self) -> Option<[u8; 3]> {
+        let [r, g, b] = self.0;
+        Some([
+            u8::try_from(r).ok()?,
+            u8::try_from(g).ok()?,
+            u8::try_from(b).ok()?,
+        ])
+    }
+
+    pub fn to_hex(self) -> Option<String> {
+        self.to_u8()
+            .map(|rgb| format!("#{}", hex::encode_upper(rgb)))
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_hex() {
+            Some(hex) => f.write_str(&hex),
+            None => write!(f, "{:?}", self.0),
+        }
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = PlayerColorError;
+
+    /// Parses a color written as `#RRGGBB`
+    fn from_str(color: &str) -> Result<Self, Self::Err> {
+        color
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| hex::decode(hex).ok())
+            .map(|rgb| Rgb([rgb[0] as u32, rgb[1] as u32, rgb[2] as u32]))
+            .ok_or_else(|| PlayerColorError::InvalidColor(color.to_string()))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RgbRepr {
+    Hex(String),
+    Channels([u32; 3]),
+}
+
+impl Serialize for Rgb {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.to_hex() {
+            Some(hex) => RgbRepr::Hex(hex),
+            None => RgbRepr::Channels(self.0),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rgb {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match RgbRepr::deserialize(deserializer)? {
+            // Older JSON files stored the color as hex without the #
+            RgbRepr::Hex(hex) if !hex.starts_with('#') => format!("#{}", hex).parse(),
+            RgbRepr::Hex(hex) => hex.parse(),
+            RgbRepr::Channels(channels) => Ok(Rgb(channels)),
+        }
+        .map_err(serde::de::Error::custom)
+    }
+}
 
 #[derive(Default, Serialize, Deserialize)]
 #[deku_derive(DekuRead, DekuWrite)]
@@ -23,25 +101,24 @@ pub struct Entry {
 
     pub costume_index: u32,
 
-    #[serde(with = "hex::serde")]
     #[deku(
         reader = "Entry::read_rgb(deku::rest, endian)",
         writer = "Entry::write_rgb(&self.rgb, deku::output, endian)"
     )]
-    pub rgb: Vec<u8>,
+    pub rgb: Rgb,
 }
 
 impl Entry {
     fn read_rgb(
         rest: &BitSlice<Msb0, u8>,
         endian: Endian,
-    ) -> Result<(&BitSlice<Msb0, u8>, Vec<u8>), DekuError> {
+    ) -> Result<(&BitSlice<Msb0, u8>, Rgb), DekuError> {
         let mut data = rest;
 
-        let mut rgb = vec![];
-        for _ in 0..3 {
+        let mut rgb = Rgb::default();
+        for channel in rgb.0.iter_mut() {
             let (rest, value) = u32::read(data, endian)?;
-            rgb.push(value as u8);
+            *channel = value;
             data = rest;
         }
 
@@ -49,18 +126,55 @@ impl Entry {
     }
 
     fn write_rgb(
-        rgb: &Vec<u8>,
+        rgb: &Rgb,
         output: &mut BitVec<Msb0, u8>,
         endian: Endian,
     ) -> Result<(), DekuError> {
-        for value in rgb.iter() {
-            u32::write(&(*value as u32), output, endian)?;
+        for value in rgb.0.iter() {
+            u32::write(value, output, endian)?;
         }
 
         Ok(())
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlayerColorError {
+    InvalidColor(String),
+    Duplicate {
+        char_code: String,
+        costume_index: u32,
+    },
+    NotFound {
+        char_code: String,
+        costume_index: u32,
+    },
+}
+
+impl fmt::Display for PlayerColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayerColorError::InvalidColor(color) => {
+                write!(f, "{:?} is not a color in #RRGGBB format", color)
+            }
+            PlayerColorError::Duplicate {
+                char_code,
+                costume_index,
+            } => write!(
+                f,
+                "a color for ({}, {}) already exists",
+                char_code, costume_index
+            ),
+            PlayerColorError::NotFound {
+                char_code,
+                costume_index,
+            } => write!(f, "no color exists for ({}, {})", char_code, costume_index),
+        }
+    }
+}
+
+impl std::error::Error for PlayerColorError {}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct PlayerColorParam {
     pub unk0: u32,
@@ -70,6 +184,122 @@ pub struct PlayerColorParam {
     big_endian: bool,
 }
 
+impl PlayerColorParam {
+    /// Character codes in the order they first appear.
+    pub fn characters(&self) -> Vec<&str> {
+        let mut characters = Vec::new();
+        for entry in self.entries.iter() {
+            if !characters.contains(&entry.char_code.as_str()) {
+                characters.push(entry.char_code.as_str());
+            }
+        }
+
+        characters
+    }
+
+    /// Indices of the entries whose character code is not in `chara_codes`.
+    pub fn unknown_chara_codes(&self, chara_codes: &CharaCodeMap) -> Vec<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !chara_codes.contains_code(&entry.char_code))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Costume indices and colors of a character, as `#RRGGBB` when they fit.
+    pub fn colors_of(&self, char_code: &str) -> Vec<(u32, String)> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.char_code == char_code)
+            .map(|entry| (entry.costume_index, entry.rgb.to_string()))
+            .collect()
+    }
+
+    fn position(&self, char_code: &str, costume_index: u32) -> Option<usize> {
+        self.entries
+            .iter()
+            .position(|entry| entry.char_code == char_code && entry.costume_index == costume_index)
+    }
+
+    /// Adds a color, keeping the entries of a character together and sorted by costume index.
+    /// Colors for characters that have none yet are added at the end.
+    pub fn add_color(
+        &mut self,
+        char_code: &str,
+        costume_index: u32,
+        color: &str,
+    ) -> Result<(), PlayerColorError> {
+        let rgb = color.parse()?;
+
+        if self.position(char_code, costume_index).is_some() {
+            return Err(PlayerColorError::Duplicate {
+                char_code: char_code.to_string(),
+                costume_index,
+            });
+        }
+
+        let same_character = |entry: &Entry| entry.char_code == char_code;
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| same_character(entry) && entry.costume_index > costume_index)
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .rposition(same_character)
+                    .map(|last| last + 1)
+            })
+            .unwrap_or(self.entries.len());
+
+        self.entries.insert(
+            index,
+            Entry {
+                char_code_pointer: 0,
+                char_code: char_code.to_string(),
+                costume_index,
+                rgb,
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn replace_color(
+        &mut self,
+        char_code: &str,
+        costume_index: u32,
+        color: &str,
+    ) -> Result<(), PlayerColorError> {
+        let rgb = color.parse()?;
+
+        let index =
+            self.position(char_code, costume_index)
+                .ok_or_else(|| PlayerColorError::NotFound {
+                    char_code: char_code.to_string(),
+                    costume_index,
+                })?;
+
+        self.entries[index].rgb = rgb;
+        Ok(())
+    }
+
+    pub fn remove_color(
+        &mut self,
+        char_code: &str,
+        costume_index: u32,
+    ) -> Result<Entry, PlayerColorError> {
+        let index =
+            self.position(char_code, costume_index)
+                .ok_or_else(|| PlayerColorError::NotFound {
+                    char_code: char_code.to_string(),
+                    costume_index,
+                })?;
+
+        Ok(self.entries.remove(index))
+    }
+}
+
 impl NuccBinaryParsed for PlayerColorParam {
     fn binary_type(&self) -> NuccBinaryType {
         NuccBinaryType::PlayerColorParam(endian_from_bool(self.big_endian))
@@ -89,6 +319,43 @@ impl NuccBinaryParsed for PlayerColorParam {
     {
         serde_json::from_slice(data).unwrap()
     }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            if entry.char_code.is_empty() {
+                diagnostics.push(Diagnostic::warning(
+                    format!("entries[{}].char_code", i),
+                    String::from("string is empty"),
+                ));
+            }
+
+            if entry.rgb.to_u8().is_none() {
+                diagnostics.push(Diagnostic::warning(
+                    format!("entries[{}].rgb", i),
+                    format!(
+                        "channels {:?} do not fit in #RRGGBB, they are kept as is",
+                        entry.rgb.0
+                    ),
+                ));
+            }
+        }
+
+        for (i, first) in
+            find_duplicates(self.entries.iter().map(|e| (&e.char_code, e.costume_index)))
+        {
+            diagnostics.push(Diagnostic::error(
+                format!("entries[{}]", i),
+                format!(
+                    "duplicate color for ({}, {}), first defined by entries[{}]",
+                    self.entries[i].char_code, self.entries[i].costume_index, first
+                ),
+            ));
+        }
+
+        diagnostics
+    }
 }
 
 impl From<(&[u8], Endian)> for PlayerColorParam {
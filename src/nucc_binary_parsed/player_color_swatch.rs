@@ -0,0 +1,271 @@
+use super::player_color_param::{PlayerColorParam, Rgb};
+
+use std::fmt::Write;
+
+const MARGIN: usize = 8;
+const SWATCH_SIZE: usize = 32;
+const GAP: usize = 8;
+
+/// Scale of the 3x5 pixel font used for labels in PNG sheets
+const FONT_SCALE: usize = 2;
+const GLYPH_WIDTH: usize = 4 * FONT_SCALE;
+const GLYPH_HEIGHT: usize = 6 * FONT_SCALE;
+
+const BACKGROUND: [u8; 3] = [0xFF, 0xFF, 0xFF];
+const BORDER: [u8; 3] = [0x80, 0x80, 0x80];
+const TEXT: [u8; 3] = [0x20, 0x20, 0x20];
+
+/// Rows of a 3x5 glyph, top to bottom, with the leftmost pixel in the highest bit.
+/// Letters are case insensitive and unknown characters are drawn as `?`.
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_lowercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'a' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'b' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'c' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'd' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'e' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'f' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'g' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'h' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'i' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'j' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'k' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'l' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'm' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'n' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'o' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'p' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'r' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        's' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        't' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'u' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'v' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'w' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'x' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ' ' => [0b000; 5],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+/// Channels above 255 are clamped, since they cannot be shown as they are
+fn display_color(rgb: Rgb) -> [u8; 3] {
+    rgb.0.map(|channel| channel.min(0xFF) as u8)
+}
+
+/// Swatches of one character, in file order
+struct SwatchRow<'a> {
+    char_code: &'a str,
+    colors: Vec<(u32, Rgb)>,
+}
+
+/// Positions shared by the PNG and SVG sheets
+struct Layout<'a> {
+    rows: Vec<SwatchRow<'a>>,
+    label_width: usize,
+
+    /// Width of a swatch and the gap after it, widened to fit the longest costume index
+    column_width: usize,
+    row_height: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Layout<'a> {
+    /// `glyph_width` is the width of a label character, including the space after it.
+    fn new(param: &'a PlayerColorParam, glyph_width: usize) -> Self {
+        let rows: Vec<SwatchRow> = param
+            .characters()
+            .into_iter()
+            .map(|char_code| SwatchRow {
+                char_code,
+                colors: param
+                    .entries
+                    .iter()
+                    .filter(|entry| entry.char_code == char_code)
+                    .map(|entry| (entry.costume_index, entry.rgb))
+                    .collect(),
+            })
+            .collect();
+
+        let label_width = rows
+            .iter()
+            .map(|row| row.char_code.chars().count() * glyph_width + GAP)
+            .max()
+            .unwrap_or(0);
+
+        let index_width = rows
+            .iter()
+            .flat_map(|row| row.colors.iter())
+            .map(|(costume_index, _)| costume_index.to_string().len() * glyph_width)
+            .max()
+            .unwrap_or(0);
+
+        let columns = rows.iter().map(|row| row.colors.len()).max().unwrap_or(0);
+        let column_width = SWATCH_SIZE.max(index_width) + GAP;
+        let row_height = SWATCH_SIZE + 2 + GLYPH_HEIGHT + GAP;
+
+        Self {
+            label_width,
+            column_width,
+            row_height,
+            width: MARGIN * 2 + label_width + columns * column_width,
+            height: MARGIN * 2 + rows.len() * row_height,
+            rows,
+        }
+    }
+
+    fn row_y(&self, row: usize) -> usize {
+        MARGIN + row * self.row_height
+    }
+
+    fn swatch_x(&self, column: usize) -> usize {
+        MARGIN + self.label_width + column * self.column_width
+    }
+}
+
+struct Canvas {
+    width: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: [u8; 3]) {
+        for row in y..y + height {
+            let start = (row * self.width + x) * 3;
+            for pixel in self.pixels[start..start + width * 3].chunks_exact_mut(3) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+
+    fn text(&mut self, x: usize, y: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        self.fill(
+                            x + i * GLYPH_WIDTH + column * FONT_SCALE,
+                            y + row * FONT_SCALE,
+                            FONT_SCALE,
+                            FONT_SCALE,
+                            TEXT,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl PlayerColorParam {
+    /// Renders every color as a PNG swatch, with one row per character and the costume index under each swatch.
+    pub fn swatch_png(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let layout = Layout::new(self, GLYPH_WIDTH);
+        let mut canvas = Canvas {
+            width: layout.width,
+            pixels: BACKGROUND.repeat(layout.width * layout.height),
+        };
+
+        for (i, row) in layout.rows.iter().enumerate() {
+            let y = layout.row_y(i);
+            canvas.text(MARGIN, y + (SWATCH_SIZE - GLYPH_HEIGHT) / 2, row.char_code);
+
+            for (column, (costume_index, rgb)) in row.colors.iter().enumerate() {
+                let x = layout.swatch_x(column);
+                canvas.fill(x, y, SWATCH_SIZE, SWATCH_SIZE, BORDER);
+                canvas.fill(
+                    x + 1,
+                    y + 1,
+                    SWATCH_SIZE - 2,
+                    SWATCH_SIZE - 2,
+                    display_color(*rgb),
+                );
+                canvas.text(x, y + SWATCH_SIZE + 2, &costume_index.to_string());
+            }
+        }
+
+        let mut output = Vec::new();
+        let mut encoder = png::Encoder::new(&mut output, layout.width as u32, layout.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&canvas.pixels)?;
+        writer.finish()?;
+
+        Ok(output)
+    }
+
+    /// Same layout as `swatch_png`, with each swatch's exact value shown as a tooltip.
+    pub fn swatch_svg(&self) -> String {
+        // Monospace glyphs are about 0.6 em wide
+        let layout = Layout::new(self, 8);
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"monospace\" font-size=\"12\">\n",
+            layout.width, layout.height
+        );
+        svg.push_str("  <rect width=\"100%\" height=\"100%\" fill=\"#FFFFFF\"/>\n");
+
+        for (i, row) in layout.rows.iter().enumerate() {
+            let y = layout.row_y(i);
+            let char_code = escape_xml(row.char_code);
+
+            writeln!(
+                svg,
+                "  <text x=\"{}\" y=\"{}\" dominant-baseline=\"middle\">{}</text>",
+                MARGIN,
+                y + SWATCH_SIZE / 2,
+                char_code
+            )
+            .unwrap();
+
+            for (column, (costume_index, rgb)) in row.colors.iter().enumerate() {
+                let x = layout.swatch_x(column);
+                let [r, g, b] = display_color(*rgb);
+
+                writeln!(
+                    svg,
+                    "  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#{:02X}{:02X}{:02X}\" stroke=\"#808080\"><title>{} {}: {}</title></rect>",
+                    x, y, SWATCH_SIZE, SWATCH_SIZE, r, g, b, char_code, costume_index, rgb
+                )
+                .unwrap();
+
+                writeln!(
+                    svg,
+                    "  <text x=\"{}\" y=\"{}\" dominant-baseline=\"hanging\">{}</text>",
+                    x,
+                    y + SWATCH_SIZE + 2,
+                    costume_index
+                )
+                .unwrap();
+            }
+        }
+
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
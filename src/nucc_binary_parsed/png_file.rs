@@ -1,13 +1,218 @@
 use super::NuccBinaryParsed;
 use super::NuccBinaryType;
+use crate::validation::Diagnostic;
 
+use crc::{Crc, CRC_32_ISO_HDLC};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+
+pub(crate) const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const CHUNK_CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// Ancillary chunks that only carry metadata, and can be removed without changing how the image looks.
+const METADATA_CHUNKS: [&str; 6] = ["tEXt", "zTXt", "iTXt", "tIME", "eXIf", "pHYs"];
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PngChunk {
+    pub chunk_type: String,
+
+    /// Offset of the chunk's length field from the start of the file
+    pub offset: usize,
+    pub length: u32,
+    pub crc: u32,
+}
+
+impl PngChunk {
+    /// Critical chunks are required to display the image, ancillary chunks are not.
+    pub fn is_critical(&self) -> bool {
+        self.chunk_type
+            .starts_with(|c: char| c.is_ascii_uppercase())
+    }
+
+    pub fn is_metadata(&self) -> bool {
+        METADATA_CHUNKS.contains(&self.chunk_type.as_str())
+    }
+
+    /// Size of the whole chunk, including its length, type and CRC.
+    pub fn size(&self) -> usize {
+        self.length as usize + 12
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PngHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+impl PngHeader {
+    pub fn color_type_name(&self) -> &'static str {
+        match self.color_type {
+            0 => "Grayscale",
+            2 => "RGB",
+            3 => "Indexed",
+            4 => "GrayscaleAlpha",
+            6 => "RGBA",
+            _ => "Unknown",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PngError {
+    InvalidSignature,
+    Truncated {
+        offset: usize,
+    },
+    CrcMismatch {
+        chunk_type: String,
+        offset: usize,
+        expected: u32,
+        actual: u32,
+    },
+    InvalidHeader,
+    MissingEnd,
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PngError::InvalidSignature => write!(f, "file does not start with the PNG signature"),
+            PngError::Truncated { offset } => {
+                write!(f, "chunk at offset {:#X} is truncated", offset)
+            }
+            PngError::CrcMismatch {
+                chunk_type,
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} chunk at offset {:#X} has CRC {:08X}, expected {:08X}",
+                chunk_type, offset, actual, expected
+            ),
+            PngError::InvalidHeader => write!(f, "first chunk is not a valid IHDR chunk"),
+            PngError::MissingEnd => write!(f, "IEND chunk is missing"),
+        }
+    }
+}
+
+impl std::error::Error for PngError {}
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct PngFile {
     pub file: Vec<u8>,
 }
 
+impl PngFile {
+    /// Lists the chunks of the file, verifying the signature and the CRC of every chunk.
+    pub fn chunks(&self) -> Result<Vec<PngChunk>, PngError> {
+        if !self.file.starts_with(PNG_SIGNATURE) {
+            return Err(PngError::InvalidSignature);
+        }
+
+        let mut chunks = Vec::new();
+        let mut offset = PNG_SIGNATURE.len();
+
+        while offset < self.file.len() {
+            let header = self
+                .file
+                .get(offset..offset + 8)
+                .ok_or(PngError::Truncated { offset })?;
+
+            let length = u32::from_be_bytes(header[..4].try_into().unwrap());
+            let end = offset + length as usize + 12;
+            let data = self
+                .file
+                .get(offset + 4..end)
+                .ok_or(PngError::Truncated { offset })?;
+
+            let (content, crc) = data.split_at(data.len() - 4);
+            let crc = u32::from_be_bytes(crc.try_into().unwrap());
+            let chunk_type = String::from_utf8_lossy(&content[..4]).to_string();
+
+            let expected = CHUNK_CRC.checksum(content);
+            if crc != expected {
+                return Err(PngError::CrcMismatch {
+                    chunk_type,
+                    offset,
+                    expected,
+                    actual: crc,
+                });
+            }
+
+            let is_end = chunk_type == "IEND";
+            chunks.push(PngChunk {
+                chunk_type,
+                offset,
+                length,
+                crc,
+            });
+
+            offset = end;
+            if is_end {
+                break;
+            }
+        }
+
+        if chunks.last().map(|chunk| chunk.chunk_type.as_str()) != Some("IEND") {
+            return Err(PngError::MissingEnd);
+        }
+
+        Ok(chunks)
+    }
+
+    pub fn header(&self) -> Result<PngHeader, PngError> {
+        let chunks = self.chunks()?;
+
+        let data = match chunks.first() {
+            Some(chunk) if chunk.chunk_type == "IHDR" && chunk.length == 13 => {
+                &self.file[chunk.offset + 8..chunk.offset + 8 + 13]
+            }
+            _ => return Err(PngError::InvalidHeader),
+        };
+
+        Ok(PngHeader {
+            width: u32::from_be_bytes(data[..4].try_into().unwrap()),
+            height: u32::from_be_bytes(data[4..8].try_into().unwrap()),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        })
+    }
+
+    pub fn ancillary_chunks(&self) -> Result<Vec<PngChunk>, PngError> {
+        Ok(self
+            .chunks()?
+            .into_iter()
+            .filter(|chunk| !chunk.is_critical())
+            .collect())
+    }
+
+    /// Removes the text, time, EXIF and physical size chunks, returning the number of bytes removed.
+    /// Anything after the IEND chunk is dropped as well.
+    pub fn strip_metadata(&mut self) -> Result<usize, PngError> {
+        let chunks = self.chunks()?;
+        let original_len = self.file.len();
+
+        let mut file = PNG_SIGNATURE.to_vec();
+        for chunk in chunks.iter().filter(|chunk| !chunk.is_metadata()) {
+            file.extend_from_slice(&self.file[chunk.offset..chunk.offset + chunk.size()]);
+        }
+
+        self.file = file;
+        Ok(original_len - self.file.len())
+    }
+}
+
 impl NuccBinaryParsed for PngFile {
     fn binary_type(&self) -> NuccBinaryType {
         NuccBinaryType::PNG
@@ -29,6 +234,68 @@ impl NuccBinaryParsed for PngFile {
             file: data.to_vec(),
         }
     }
+
+    fn info(&self) -> serde_json::Value {
+        let chunks = match self.chunks() {
+            Ok(chunks) => chunks,
+            Err(e) => {
+                return json!({
+                    "type": self.binary_type().to_string(),
+                    "size": self.file.len(),
+                    "error": e.to_string(),
+                })
+            }
+        };
+
+        let header = self.header().ok();
+        json!({
+            "type": self.binary_type().to_string(),
+            "size": self.file.len(),
+            "width": header.as_ref().map(|header| header.width),
+            "height": header.as_ref().map(|header| header.height),
+            "bit_depth": header.as_ref().map(|header| header.bit_depth),
+            "color_type": header.as_ref().map(|header| header.color_type_name()),
+            "ancillary_chunks": chunks
+                .iter()
+                .filter(|chunk| !chunk.is_critical())
+                .map(|chunk| chunk.chunk_type.as_str())
+                .collect::<Vec<_>>(),
+            "chunks": chunks,
+        })
+    }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let chunks = match self.chunks() {
+            Ok(chunks) => chunks,
+            Err(e) => return vec![Diagnostic::error(String::from("file"), e.to_string())],
+        };
+
+        let mut diagnostics = Vec::new();
+
+        if let Err(e) = self.header() {
+            diagnostics.push(Diagnostic::error(String::from("chunks[0]"), e.to_string()));
+        }
+
+        if !chunks.iter().any(|chunk| chunk.chunk_type == "IDAT") {
+            diagnostics.push(Diagnostic::error(
+                String::from("chunks"),
+                String::from("IDAT chunk is missing"),
+            ));
+        }
+
+        let end = chunks.last().unwrap();
+        if end.offset + end.size() < self.file.len() {
+            diagnostics.push(Diagnostic::warning(
+                String::from("file"),
+                format!(
+                    "{} bytes of trailing data after the IEND chunk",
+                    self.file.len() - end.offset - end.size()
+                ),
+            ));
+        }
+
+        diagnostics
+    }
 }
 
 impl From<&[u8]> for PngFile {
@@ -4,13 +4,79 @@ use super::NuccBinaryType;
 
 use deku::bitvec::BitVec;
 use deku::bitvec::BitView;
+use deku::bitvec::{BitSlice, Msb0};
 use deku::ctx::Endian;
 use deku::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-use crate::utils::DekuFixedString;
+use crate::utils::{
+    fit_fixed_string, fixed_string_bytes, DekuFixedString, FixedStringOverflow, OverflowPolicy,
+};
+use crate::validation::{check_string, Diagnostic};
 
-#[derive(Default, Serialize, Deserialize)]
+/// Type of a file loaded by an entry. Values whose meaning has not been confirmed are kept as
+/// `Unknown`, which round-trips through JSON as the plain number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "u32", into = "u32")]
+pub enum FileType {
+    Unknown(u32),
+}
+
+impl Default for FileType {
+    fn default() -> Self {
+        FileType::Unknown(0)
+    }
+}
+
+impl From<u32> for FileType {
+    fn from(value: u32) -> Self {
+        FileType::Unknown(value)
+    }
+}
+
+impl From<FileType> for u32 {
+    fn from(file_type: FileType) -> Self {
+        match file_type {
+            FileType::Unknown(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for FileType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileType::Unknown(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PrmLoadError {
+    TemplateNotFound(String),
+    TooLong(FixedStringOverflow),
+}
+
+impl fmt::Display for PrmLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrmLoadError::TemplateNotFound(file_name) => {
+                write!(f, "no entry has the file name {:?}", file_name)
+            }
+            PrmLoadError::TooLong(overflow) => write!(f, "{}", overflow),
+        }
+    }
+}
+
+impl std::error::Error for PrmLoadError {}
+
+impl From<FixedStringOverflow> for PrmLoadError {
+    fn from(overflow: FixedStringOverflow) -> Self {
+        PrmLoadError::TooLong(overflow)
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[deku_derive(DekuRead, DekuWrite)]
 #[deku(
     endian = "endian",
@@ -18,17 +84,31 @@ use crate::utils::DekuFixedString;
     ctx_default = "Endian::Little"
 )]
 pub struct Entry {
-    #[deku(ctx = "0x20")]
+    #[deku(ctx = "\"folder_name\", 0x20")]
     pub folder_name: DekuFixedString,
 
-    #[deku(ctx = "0x20")]
+    #[deku(ctx = "\"file_name\", 0x20")]
     pub file_name: DekuFixedString,
 
-    pub file_type: u32,
+    #[deku(
+        reader = "Entry::read_file_type(deku::rest, endian)",
+        writer = "u32::from(self.file_type).write(deku::output, endian)"
+    )]
+    pub file_type: FileType,
     pub unk1: u32,
 }
 
-#[derive(Default, Serialize, Deserialize)]
+impl Entry {
+    fn read_file_type(
+        rest: &BitSlice<Msb0, u8>,
+        endian: Endian,
+    ) -> Result<(&BitSlice<Msb0, u8>, FileType), DekuError> {
+        let (rest, value) = u32::read(rest, endian)?;
+        Ok((rest, FileType::from(value)))
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[deku_derive(DekuRead, DekuWrite)]
 #[deku(
     endian = "endian",
@@ -66,19 +146,141 @@ impl NuccBinaryParsed for PrmLoad {
     {
         serde_json::from_slice(data).unwrap()
     }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            check_string(
+                &mut diagnostics,
+                format!("entries[{}].folder_name", i),
+                &entry.folder_name.string,
+                0x20,
+            );
+            check_string(
+                &mut diagnostics,
+                format!("entries[{}].file_name", i),
+                &entry.file_name.string,
+                0x20,
+            );
+        }
+
+        diagnostics
+    }
 }
 
 impl PrmLoad {
+    /// Character code of a `prm_load.bin` path, taken from the start of its file name (`0bao01prm_load.bin`).
+    pub fn chara_code_of_path(path: &str) -> Option<&str> {
+        let file_name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+        let code = file_name
+            .strip_suffix("prm_load.bin")?
+            .trim_end_matches('_');
+
+        (!code.is_empty()).then_some(code)
+    }
+
     pub fn read_parsed(data: &[u8], endian: Endian) -> Self {
         Self::read(data.view_bits(), endian).unwrap().1
     }
 
     pub fn write_parsed(&mut self) -> Vec<u8> {
-        self.update().unwrap();
+        self.try_write_parsed().unwrap()
+    }
+
+    pub fn try_write_parsed(&mut self) -> Result<Vec<u8>, DekuError> {
+        self.update()?;
 
         let mut output = BitVec::new();
-        self.write(&mut output, endian_from_bool(self.big_endian))
-            .unwrap();
-        output.into_vec()
+        self.write(&mut output, endian_from_bool(self.big_endian))?;
+        Ok(output.into_vec())
+    }
+
+    /// Makes the folder and file names fit in their 0x20 byte fields according to `policy`.
+    /// Returns the names that were truncated.
+    pub fn fit_strings(
+        &mut self,
+        policy: OverflowPolicy,
+    ) -> Result<Vec<FixedStringOverflow>, FixedStringOverflow> {
+        let mut truncated = Vec::new();
+
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            for (field, string) in [
+                ("folder_name", &mut entry.folder_name.string),
+                ("file_name", &mut entry.file_name.string),
+            ] {
+                let field = format!("entries[{}].{}", i, field);
+                truncated.extend(fit_fixed_string(&field, string, 0x20, policy)?);
+            }
+        }
+
+        Ok(truncated)
+    }
+
+    pub fn entries_of_type(&self, file_type: FileType) -> impl Iterator<Item = &Entry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.file_type == file_type)
+    }
+
+    /// Distinct file types used by the entries, in order of first use.
+    pub fn file_types(&self) -> Vec<FileType> {
+        let mut file_types = Vec::new();
+        for entry in self.entries.iter() {
+            if !file_types.contains(&entry.file_type) {
+                file_types.push(entry.file_type);
+            }
+        }
+
+        file_types
+    }
+
+    /// Appends an entry and returns its index.
+    pub fn add_entry(
+        &mut self,
+        folder_name: &str,
+        file_name: &str,
+        file_type: FileType,
+        unk1: u32,
+    ) -> Result<usize, FixedStringOverflow> {
+        let index = self.entries.len();
+        fixed_string_bytes(
+            &format!("entries[{}].folder_name", index),
+            folder_name,
+            0x20,
+        )?;
+        fixed_string_bytes(&format!("entries[{}].file_name", index), file_name, 0x20)?;
+
+        self.entries.push(Entry {
+            folder_name: DekuFixedString {
+                string: folder_name.to_string(),
+            },
+            file_name: DekuFixedString {
+                string: file_name.to_string(),
+            },
+            file_type,
+            unk1,
+        });
+        self.entry_count = self.entries.len() as u32;
+
+        Ok(index)
+    }
+
+    /// Appends an entry with the same `file_type` and `unk1` as the entry named `template`,
+    /// such as `2nrtbod1` for another character's model file.
+    pub fn add_entry_like(
+        &mut self,
+        folder_name: &str,
+        file_name: &str,
+        template: &str,
+    ) -> Result<usize, PrmLoadError> {
+        let (file_type, unk1) = self
+            .entries
+            .iter()
+            .find(|entry| entry.file_name.string == template)
+            .map(|entry| (entry.file_type, entry.unk1))
+            .ok_or_else(|| PrmLoadError::TemplateNotFound(template.to_string()))?;
+
+        Ok(self.add_entry(folder_name, file_name, file_type, unk1)?)
     }
 }
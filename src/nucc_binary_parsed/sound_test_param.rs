@@ -2,6 +2,7 @@ use super::calc_crc32;
 use super::endian_from_bool;
 use super::NuccBinaryParsed;
 use super::NuccBinaryType;
+use crate::validation::Diagnostic;
 
 use binary_stream::SeekStream;
 use binary_stream::{BinaryReader, BinaryWriter, MemoryStream, SliceStream};
@@ -11,7 +12,7 @@ use deku::ctx::Endian;
 use deku::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[deku_derive(DekuRead, DekuWrite)]
 #[deku(
     endian = "endian",
@@ -91,6 +92,21 @@ impl NuccBinaryParsed for SoundTestParam {
     {
         serde_json::from_slice(data).unwrap()
     }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            if entry.entry_name.is_empty() {
+                diagnostics.push(Diagnostic::warning(
+                    format!("entries[{}].entry_name", i),
+                    String::from("string is empty"),
+                ));
+            }
+        }
+
+        diagnostics
+    }
 }
 
 impl From<(&[u8], Endian)> for SoundTestParam {
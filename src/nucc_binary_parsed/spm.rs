@@ -0,0 +1,352 @@
+use super::xml_file::{
+    escape, parse_tree, qualified_name, XmlDocument, XmlElement, XmlError, XmlFile, XmlNode,
+};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SpmValue {
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl SpmValue {
+    pub fn parse(text: &str) -> Self {
+        let trimmed = text.trim();
+
+        if let Ok(value) = trimmed.parse::<bool>() {
+            return SpmValue::Boolean(value);
+        }
+
+        if let Ok(value) = trimmed.parse::<i64>() {
+            return SpmValue::Integer(value);
+        }
+
+        // Only plain decimal numbers, not "inf" or "NaN"
+        if trimmed.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') {
+            if let Ok(value) = trimmed.parse::<f64>() {
+                return SpmValue::Float(value);
+            }
+        }
+
+        SpmValue::Text(text.to_string())
+    }
+}
+
+impl fmt::Display for SpmValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpmValue::Boolean(value) => write!(f, "{}", value),
+            SpmValue::Integer(value) => write!(f, "{}", value),
+            SpmValue::Float(value) => write!(f, "{:?}", value),
+            SpmValue::Text(value) => f.write_str(value),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpmParameter {
+    pub name: String,
+    pub value: SpmValue,
+}
+
+/// A move is a child element of the root. Its parameters are its attributes,
+/// followed by its child elements that only contain text.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpmMove {
+    /// Position of the move among the root's child elements
+    pub index: usize,
+    pub element: String,
+    pub parameters: Vec<SpmParameter>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpmError {
+    Xml(XmlError),
+    MissingRoot,
+    MoveNotFound(usize),
+    ParameterNotFound { index: usize, name: String },
+}
+
+impl fmt::Display for SpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpmError::Xml(e) => write!(f, "{}", e),
+            SpmError::MissingRoot => write!(f, "document has no root element"),
+            SpmError::MoveNotFound(index) => write!(f, "move {} does not exist", index),
+            SpmError::ParameterNotFound { index, name } => {
+                write!(f, "move {} has no parameter {:?}", index, name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpmError {}
+
+impl From<XmlError> for SpmError {
+    fn from(e: XmlError) -> Self {
+        SpmError::Xml(e)
+    }
+}
+
+/// Text of an element whose children are all text, or `None` if it has child elements.
+fn leaf_text(element: &XmlElement) -> Option<String> {
+    element
+        .children
+        .iter()
+        .map(|child| match child {
+            XmlNode::Text { text } => Some(text.as_str()),
+            XmlNode::Comment { .. } => Some(""),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether an attribute of the DOM is a namespace declaration rather than a parameter.
+fn is_namespace_declaration(name: &str) -> bool {
+    name == "xmlns" || name.starts_with("xmlns:")
+}
+
+/// Range of the raw value of an attribute between its quotes, from the position of its name,
+/// along with the quote character.
+fn attribute_value_range(text: &str, position: usize) -> (Range<usize>, char) {
+    let quote_start = position + text[position..].find(['"', '\'']).unwrap();
+    let quote = text[quote_start..].chars().next().unwrap();
+
+    let start = quote_start + 1;
+    let end = start + text[start..].find(quote).unwrap();
+    (start..end, quote)
+}
+
+fn move_node<'a, 'input>(
+    tree: &'a roxmltree::Document<'input>,
+    index: usize,
+) -> Result<roxmltree::Node<'a, 'input>, SpmError> {
+    tree.root_element()
+        .children()
+        .filter(|node| node.is_element())
+        .nth(index)
+        .ok_or(SpmError::MoveNotFound(index))
+}
+
+/// Where the value of a parameter is in the text of the document.
+enum Location {
+    /// Raw value of an attribute, between its quotes
+    Attribute(Range<usize>, char),
+
+    /// Content of a text-only element, between its tags
+    Content(Range<usize>),
+
+    /// The `/>` of a text-only element written as an empty element tag
+    EmptyElement(Range<usize>, String),
+}
+
+/// Typed view of an SPM document. Element and parameter names are taken from the file,
+/// since the SPM layout has not been confirmed, and values are typed by parsing their text.
+/// Edits are made on the text of the file in place, so the declaration, DTD, formatting,
+/// element order and anything the model does not cover are kept.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Spm {
+    text: String,
+    document: XmlDocument,
+}
+
+impl Spm {
+    pub fn from_xml(xml: &XmlFile) -> Result<Self, SpmError> {
+        Self::from_text(xml.text()?)
+    }
+
+    fn from_text(text: String) -> Result<Self, SpmError> {
+        let document = XmlDocument::parse(&text)?;
+        document.root().ok_or(SpmError::MissingRoot)?;
+
+        Ok(Self { text, document })
+    }
+
+    pub fn document(&self) -> &XmlDocument {
+        &self.document
+    }
+
+    /// Writes the edited text back into `xml`, keeping its encoding.
+    pub fn write_to(&self, xml: &mut XmlFile) -> Result<(), SpmError> {
+        Ok(xml.set_text(&self.text)?)
+    }
+
+    /// Replaces a range of the text and parses it again.
+    fn splice(&mut self, range: Range<usize>, replacement: &str) -> Result<(), SpmError> {
+        let mut text = self.text.clone();
+        text.replace_range(range, replacement);
+
+        *self = Self::from_text(text)?;
+        Ok(())
+    }
+
+    fn locate(&self, index: usize, name: &str) -> Result<Location, SpmError> {
+        let tree = parse_tree(&self.text)?;
+        let element = move_node(&tree, index)?;
+
+        if let Some(attribute) = element.attributes().find(|attribute| {
+            qualified_name(element, attribute.namespace(), attribute.name()) == name
+        }) {
+            let (range, quote) = attribute_value_range(&self.text, attribute.position());
+            return Ok(Location::Attribute(range, quote));
+        }
+
+        let child = element
+            .children()
+            .find(|child| {
+                child.is_element()
+                    && qualified_name(
+                        *child,
+                        child.tag_name().namespace(),
+                        child.tag_name().name(),
+                    ) == name
+                    && child
+                        .children()
+                        .all(|node| node.is_text() || node.is_comment())
+            })
+            .ok_or_else(|| SpmError::ParameterNotFound {
+                index,
+                name: name.to_string(),
+            })?;
+
+        let range = child.range();
+        Ok(match (child.first_child(), child.last_child()) {
+            (Some(first), Some(last)) => Location::Content(first.range().start..last.range().end),
+            _ if self.text[range.clone()].ends_with("/>") => {
+                Location::EmptyElement(range.end - 2..range.end, name.to_string())
+            }
+            _ => {
+                let end_tag = range.start + self.text[range].rfind("</").unwrap();
+                Location::Content(end_tag..end_tag)
+            }
+        })
+    }
+
+    pub fn moves(&self) -> Vec<SpmMove> {
+        let root = match self.document.root() {
+            Some(root) => root,
+            None => return Vec::new(),
+        };
+
+        root.elements()
+            .enumerate()
+            .map(|(index, element)| {
+                let attributes = element
+                    .attributes
+                    .iter()
+                    .filter(|attribute| !is_namespace_declaration(&attribute.name))
+                    .map(|attribute| SpmParameter {
+                        name: attribute.name.clone(),
+                        value: SpmValue::parse(&attribute.value),
+                    });
+
+                let children = element.elements().filter_map(|child| {
+                    leaf_text(child).map(|text| SpmParameter {
+                        name: child.name.clone(),
+                        value: SpmValue::parse(&text),
+                    })
+                });
+
+                SpmMove {
+                    index,
+                    element: element.name.clone(),
+                    parameters: attributes.chain(children).collect(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn parameter(&self, index: usize, name: &str) -> Option<SpmValue> {
+        self.moves()
+            .into_iter()
+            .nth(index)?
+            .parameters
+            .into_iter()
+            .find(|parameter| parameter.name == name)
+            .map(|parameter| parameter.value)
+    }
+
+    /// Sets an attribute or a text-only child element of a move.
+    /// Values that are equal to the current one are left untouched, so their formatting is kept.
+    pub fn set_parameter(
+        &mut self,
+        index: usize,
+        name: &str,
+        value: &SpmValue,
+    ) -> Result<(), SpmError> {
+        let location = self.locate(index, name)?;
+        if self.parameter(index, name).as_ref() == Some(value) {
+            return Ok(());
+        }
+
+        let text = value.to_string();
+        match location {
+            Location::Attribute(range, quote) => {
+                let mut escaped = escape(&text, true);
+                if quote == '\'' {
+                    escaped = escaped.replace('\'', "&apos;");
+                }
+
+                self.splice(range, &escaped)
+            }
+            Location::Content(range) => self.splice(range, &escape(&text, false)),
+            Location::EmptyElement(range, name) => {
+                self.splice(range, &format!(">{}</{}>", escape(&text, false), name))
+            }
+        }
+    }
+
+    /// Adds a parameter as an attribute of a move, after its existing attributes, or sets it if it already exists.
+    pub fn insert_parameter(
+        &mut self,
+        index: usize,
+        name: &str,
+        value: &SpmValue,
+    ) -> Result<(), SpmError> {
+        match self.set_parameter(index, name, value) {
+            Err(SpmError::ParameterNotFound { .. }) => {
+                let position = {
+                    let tree = parse_tree(&self.text)?;
+                    let element = move_node(&tree, index)?;
+
+                    match element.attributes().next_back() {
+                        Some(attribute) => {
+                            attribute_value_range(&self.text, attribute.position())
+                                .0
+                                .end
+                                + 1
+                        }
+                        None => {
+                            let start = element.range().start + 1;
+                            start
+                                + self.text[start..]
+                                    .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                                    .unwrap()
+                        }
+                    }
+                };
+
+                let attribute = format!(" {}=\"{}\"", name, escape(&value.to_string(), true));
+                self.splice(position..position, &attribute)
+            }
+            result => result,
+        }
+    }
+
+    /// Applies the parameters of edited moves, such as ones read back from JSON.
+    pub fn apply(&mut self, moves: &[SpmMove]) -> Result<(), SpmError> {
+        for spm_move in moves {
+            for parameter in spm_move.parameters.iter() {
+                self.set_parameter(spm_move.index, &parameter.name, &parameter.value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 use super::endian_from_bool;
 use super::NuccBinaryParsed;
 use super::NuccBinaryType;
+use crate::validation::{check_float, check_floats, Diagnostic};
 
 use binary_stream::SeekStream;
 use binary_stream::{BinaryReader, BinaryWriter, MemoryStream, SliceStream};
@@ -126,6 +127,45 @@ impl NuccBinaryParsed for StageInfo {
     {
         serde_json::from_slice(data).unwrap()
     }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            if entry.entry_name.is_empty() {
+                diagnostics.push(Diagnostic::warning(
+                    format!("entries[{}].entry_name", i),
+                    String::from("string is empty"),
+                ));
+            }
+
+            check_floats(
+                &mut diagnostics,
+                format!("entries[{}].unk_vec", i),
+                &entry.unk_vec,
+            );
+            check_floats(
+                &mut diagnostics,
+                format!("entries[{}].unk_floats0", i),
+                &entry.unk_floats0,
+            );
+            check_floats(
+                &mut diagnostics,
+                format!("entries[{}].unk_floats1", i),
+                &entry.unk_floats1,
+            );
+
+            for (j, prop) in entry.props.iter().enumerate() {
+                check_float(
+                    &mut diagnostics,
+                    format!("entries[{}].props[{}].unk1_float", i, j),
+                    prop.unk1_float,
+                );
+            }
+        }
+
+        diagnostics
+    }
 }
 
 impl From<(&[u8], Endian)> for StageInfo {
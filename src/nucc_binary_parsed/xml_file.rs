@@ -1,13 +1,417 @@
 use super::NuccBinaryParsed;
 use super::NuccBinaryType;
+use crate::validation::Diagnostic;
 
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XmlAttribute {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XmlElement {
+    /// Name including its namespace prefix, if any
+    pub name: String,
+
+    /// Attributes in document order, namespace declarations included
+    pub attributes: Vec<XmlAttribute>,
+    pub children: Vec<XmlNode>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum XmlNode {
+    Element(XmlElement),
+    Text {
+        text: String,
+    },
+    Comment {
+        text: String,
+    },
+    ProcessingInstruction {
+        target: String,
+        value: Option<String>,
+    },
+}
+
+/// Owned DOM of an XML file. The XML declaration and the DTD are not part of it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XmlDocument {
+    pub children: Vec<XmlNode>,
+}
+
+impl XmlElement {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+            .map(|attribute| attribute.value.as_str())
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|child| match child {
+            XmlNode::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+
+    fn from_node(node: roxmltree::Node) -> Self {
+        let prefixed = |namespace, name| qualified_name(node, namespace, name);
+
+        let inherited = node
+            .parent_element()
+            .map(|parent| parent.namespaces().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let declarations = node
+            .namespaces()
+            .filter(|namespace| namespace.name() != Some("xml") && !inherited.contains(namespace))
+            .map(|namespace| XmlAttribute {
+                name: match namespace.name() {
+                    Some(prefix) => format!("xmlns:{}", prefix),
+                    None => String::from("xmlns"),
+                },
+                value: namespace.uri().to_string(),
+            });
+
+        let attributes = node.attributes().map(|attribute| XmlAttribute {
+            name: prefixed(attribute.namespace(), attribute.name()),
+            value: attribute.value().to_string(),
+        });
+
+        Self {
+            name: prefixed(node.tag_name().namespace(), node.tag_name().name()),
+            attributes: declarations.chain(attributes).collect(),
+            children: node.children().filter_map(XmlNode::from_node).collect(),
+        }
+    }
+}
+
+/// Name with the namespace prefix that is in scope at `node`, if any.
+pub(crate) fn qualified_name(node: roxmltree::Node, namespace: Option<&str>, name: &str) -> String {
+    match namespace.and_then(|uri| node.lookup_prefix(uri)) {
+        Some(prefix) if !prefix.is_empty() => format!("{}:{}", prefix, name),
+        _ => name.to_string(),
+    }
+}
+
+impl XmlNode {
+    fn from_node(node: roxmltree::Node) -> Option<Self> {
+        match node.node_type() {
+            roxmltree::NodeType::Element => Some(XmlNode::Element(XmlElement::from_node(node))),
+            roxmltree::NodeType::Text => Some(XmlNode::Text {
+                text: node.text().unwrap_or_default().to_string(),
+            }),
+            roxmltree::NodeType::Comment => Some(XmlNode::Comment {
+                text: node.text().unwrap_or_default().to_string(),
+            }),
+            roxmltree::NodeType::PI => node.pi().map(|pi| XmlNode::ProcessingInstruction {
+                target: pi.target.to_string(),
+                value: pi.value.map(String::from),
+            }),
+            roxmltree::NodeType::Root => None,
+        }
+    }
+}
+
+/// Parses UTF-8 text, reporting where it is not well-formed.
+pub(crate) fn parse_tree(text: &str) -> Result<roxmltree::Document<'_>, XmlError> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+
+    roxmltree::Document::parse_with_options(text, options).map_err(|e| XmlError::Parse {
+        line: e.pos().row,
+        column: e.pos().col,
+        message: e.to_string(),
+    })
+}
+
+impl XmlDocument {
+    /// Parses UTF-8 text, such as the output of `XmlFile::text`.
+    pub fn parse(text: &str) -> Result<Self, XmlError> {
+        let tree = parse_tree(text)?;
+
+        Ok(Self {
+            children: tree
+                .root()
+                .children()
+                .filter_map(XmlNode::from_node)
+                .collect(),
+        })
+    }
+
+    pub fn root(&self) -> Option<&XmlElement> {
+        self.children.iter().find_map(|child| match child {
+            XmlNode::Element(element) => Some(element),
+            _ => None,
+        })
+    }
+}
+
+pub(crate) fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '\n' if attribute => escaped.push_str("&#10;"),
+            '\t' if attribute => escaped.push_str("&#9;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+impl fmt::Display for XmlNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XmlNode::Element(element) => {
+                write!(f, "<{}", element.name)?;
+                for attribute in element.attributes.iter() {
+                    write!(
+                        f,
+                        " {}=\"{}\"",
+                        attribute.name,
+                        escape(&attribute.value, true)
+                    )?;
+                }
+
+                if element.children.is_empty() {
+                    return write!(f, "/>");
+                }
+
+                write!(f, ">")?;
+                for child in element.children.iter() {
+                    write!(f, "{}", child)?;
+                }
+
+                write!(f, "</{}>", element.name)
+            }
+            XmlNode::Text { text } => write!(f, "{}", escape(text, false)),
+            XmlNode::Comment { text } => write!(f, "<!--{}-->", text),
+            XmlNode::ProcessingInstruction { target, value } => match value {
+                Some(value) => write!(f, "<?{} {}?>", target, value),
+                None => write!(f, "<?{}?>", target),
+            },
+        }
+    }
+}
+
+impl fmt::Display for XmlDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in self.children.iter() {
+            write!(f, "{}", child)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum XmlError {
+    /// The bytes are not valid in the file's encoding
+    Decode { encoding: String },
+
+    /// The text has a character that the target encoding cannot represent
+    Encode { encoding: String, character: char },
+    Parse {
+        line: u32,
+        column: u32,
+        message: String,
+    },
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XmlError::Decode { encoding } => write!(f, "file is not valid {}", encoding),
+            XmlError::Encode {
+                encoding,
+                character,
+            } => write!(
+                f,
+                "character {:?} (U+{:04X}) cannot be encoded as {}",
+                character, *character as u32, encoding
+            ),
+            XmlError::Parse { message, .. } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for XmlError {}
+
+/// Value of the `encoding` pseudo-attribute of the XML declaration, if there is one.
+fn declared_encoding(data: &[u8]) -> Option<String> {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    if !data.starts_with(b"<?xml") {
+        return None;
+    }
+
+    let end = data.windows(2).position(|window| window == b"?>")?;
+    let declaration = String::from_utf8_lossy(&data[..end]);
+
+    let rest = declaration.split_once("encoding")?.1.trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let (value, _) = rest[1..].split_once(quote)?;
+
+    Some(value.to_string())
+}
+
+/// Text whose XML declaration names `encoding`, adding or fixing the declaration if needed,
+/// so that a file whose encoding only came from its byte order mark keeps it after transcoding.
+fn declare_encoding(text: &str, encoding: &'static Encoding) -> String {
+    let declared =
+        declared_encoding(text.as_bytes()).and_then(|label| Encoding::for_label(label.as_bytes()));
+    if declared == Some(encoding) {
+        return text.to_string();
+    }
+
+    let attribute = format!(" encoding=\"{}\"", encoding.name());
+    let end = match text.find("?>") {
+        Some(end) if text.starts_with("<?xml") => end,
+        _ => return format!("<?xml version=\"1.0\"{}?>\n{}", attribute, text),
+    };
+
+    // Range of a pseudo-attribute, from its name to the closing quote of its value
+    let pseudo_attribute = |name: &str| {
+        let start = text[..end].find(name)?;
+        let quote_start = start + text[start..end].find(['"', '\''])?;
+        let quote = text[quote_start..].chars().next()?;
+        let quote_end = quote_start + 1 + text[quote_start + 1..end].find(quote)?;
+        Some(start..quote_end + 1)
+    };
+
+    let mut declared = text.to_string();
+    match (pseudo_attribute("encoding"), pseudo_attribute("version")) {
+        (Some(range), _) => declared.replace_range(range, attribute.trim_start()),
+        (None, Some(range)) => declared.insert_str(range.end, &attribute),
+        (None, None) => declared.insert_str("<?xml".len(), &attribute),
+    }
+
+    declared
+}
+
+fn encode(text: &str, encoding: &'static Encoding) -> Result<Vec<u8>, XmlError> {
+    // encoding_rs only decodes UTF-16, so it is encoded by hand
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        let mut data = Vec::with_capacity(text.len() * 2 + 2);
+        for unit in std::iter::once(0xFEFF).chain(text.encode_utf16()) {
+            data.extend(if encoding == UTF_16LE {
+                unit.to_le_bytes()
+            } else {
+                unit.to_be_bytes()
+            });
+        }
+
+        return Ok(data);
+    }
+
+    let (data, _, had_errors) = encoding.encode(text);
+    if had_errors {
+        let character = text
+            .chars()
+            .find(|c| encoding.encode(c.encode_utf8(&mut [0; 4])).2)
+            .unwrap();
+
+        return Err(XmlError::Encode {
+            encoding: encoding.name().to_string(),
+            character,
+        });
+    }
+
+    Ok(data.into_owned())
+}
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct XmlFile {
     pub file: Vec<u8>,
 }
 
+impl XmlFile {
+    pub fn declared_encoding(&self) -> Option<String> {
+        declared_encoding(&self.file)
+    }
+
+    /// Encoding of the file, taken from the byte order mark or the XML declaration.
+    /// Defaults to UTF-8 when neither is present or the declared encoding is unknown.
+    pub fn encoding(&self) -> &'static Encoding {
+        if let Some((encoding, _)) = Encoding::for_bom(&self.file) {
+            return encoding;
+        }
+
+        self.declared_encoding()
+            .and_then(|label| Encoding::for_label(label.as_bytes()))
+            .unwrap_or(UTF_8)
+    }
+
+    /// Contents of the file transcoded to UTF-8, without the byte order mark.
+    pub fn text(&self) -> Result<String, XmlError> {
+        let encoding = self.encoding();
+        let bom_length = Encoding::for_bom(&self.file).map_or(0, |(_, length)| length);
+
+        encoding
+            .decode_without_bom_handling_and_without_replacement(&self.file[bom_length..])
+            .map(|text| text.into_owned())
+            .ok_or_else(|| XmlError::Decode {
+                encoding: encoding.name().to_string(),
+            })
+    }
+
+    /// Encodes UTF-8 text using the encoding named in its XML declaration.
+    pub fn from_text(text: &str) -> Result<Self, XmlError> {
+        let encoding = declared_encoding(text.as_bytes())
+            .and_then(|label| Encoding::for_label(label.as_bytes()))
+            .unwrap_or(UTF_8);
+
+        Ok(Self {
+            file: encode(text, encoding)?,
+        })
+    }
+
+    /// Replaces the contents with UTF-8 text, encoded like the current contents (including a UTF-8 byte order mark).
+    pub fn set_text(&mut self, text: &str) -> Result<(), XmlError> {
+        let encoding = self.encoding();
+        let mut file = match Encoding::for_bom(&self.file) {
+            Some((bom, length)) if bom == UTF_8 => self.file[..length].to_vec(),
+            _ => Vec::new(),
+        };
+
+        file.extend(encode(text, encoding)?);
+        self.file = file;
+        Ok(())
+    }
+
+    /// Parses the file, reporting where it is not well-formed.
+    pub fn document(&self) -> Result<XmlDocument, XmlError> {
+        XmlDocument::parse(&self.text()?)
+    }
+
+    /// Writes the document back with an XML declaration for the encoding of this file.
+    /// The file is regenerated, so the DTD and the original formatting are not kept.
+    pub fn set_document(&mut self, document: &XmlDocument) -> Result<(), XmlError> {
+        let encoding = self.encoding();
+        let text = format!(
+            "<?xml version=\"1.0\" encoding=\"{}\"?>\n{}",
+            encoding.name(),
+            document
+        );
+
+        self.file = encode(&text, encoding)?;
+        Ok(())
+    }
+}
+
 impl NuccBinaryParsed for XmlFile {
     fn binary_type(&self) -> NuccBinaryType {
         NuccBinaryType::XML
@@ -17,18 +421,73 @@ impl NuccBinaryParsed for XmlFile {
         String::from(".xml")
     }
 
-    fn serialize(&self, _: bool) -> Vec<u8> {
-        self.file.clone()
+    /// With `use_json`, the file is transcoded to UTF-8 with a declaration naming its encoding
+    /// (added if the encoding only came from the byte order mark), so `deserialize` can encode it back.
+    fn serialize(&self, use_json: bool) -> Vec<u8> {
+        let encoding = self.encoding();
+        if !use_json || encoding == UTF_8 {
+            return self.file.clone();
+        }
+
+        self.text()
+            .map(|text| declare_encoding(&text, encoding).into_bytes())
+            .unwrap_or_else(|_| self.file.clone())
     }
 
-    fn deserialize(data: &[u8], _: bool) -> Self
+    fn deserialize(data: &[u8], use_json: bool) -> Self
     where
         Self: Sized,
     {
+        // Text that cannot be encoded is kept as it is, and reported by `validate`
+        if use_json {
+            if let Ok(Ok(parsed)) = std::str::from_utf8(data).map(Self::from_text) {
+                return parsed;
+            }
+        }
+
         Self {
             file: data.to_vec(),
         }
     }
+
+    fn info(&self) -> serde_json::Value {
+        let document = self.document();
+
+        json!({
+            "type": self.binary_type().to_string(),
+            "size": self.file.len(),
+            "declared_encoding": self.declared_encoding(),
+            "encoding": self.encoding().name(),
+            "root": document.as_ref().ok().and_then(|document| document.root()).map(|root| &root.name),
+            "error": document.as_ref().err().map(|e| e.to_string()),
+        })
+    }
+
+    fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        if let Some(label) = self.declared_encoding() {
+            if Encoding::for_label(label.as_bytes()).is_none() {
+                diagnostics.push(Diagnostic::warning(
+                    String::from("file"),
+                    format!("unknown encoding {:?} is declared, assuming UTF-8", label),
+                ));
+            }
+        }
+
+        // UTF-8 text declaring another encoding, such as a transcoded file that could not be encoded back
+        if self.encoding() != UTF_8 {
+            if let Ok(Err(e)) = std::str::from_utf8(&self.file).map(Self::from_text) {
+                diagnostics.push(Diagnostic::error(String::from("file"), e.to_string()));
+            }
+        }
+
+        if let Err(e) = self.document() {
+            diagnostics.push(Diagnostic::error(String::from("file"), e.to_string()));
+        }
+
+        diagnostics
+    }
 }
 
 impl From<&[u8]> for XmlFile {
@@ -1,19 +1,113 @@
+use deku::bitvec::{BitVec, Msb0};
 use deku::ctx::{Endian, Limit};
 use deku::prelude::*;
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 #[deku_derive(DekuRead, DekuWrite)]
-#[deku(ctx = "_: Endian, size: usize")]
+#[deku(ctx = "_: Endian, field: &'static str, size: usize")]
 pub struct DekuFixedString {
     #[deku(
         reader = "Vec::<u8>::read(deku::rest, Limit::from(size)).map(|(r, s)| (r, String::from_utf8(s).unwrap().trim_end_matches(\'\0\').to_string()))",
-        writer = "(string.clone() + &String::from(\"\0\").repeat(size - string.len())).as_bytes().write(deku::output, ())"
+        writer = "DekuFixedString::write_fixed(deku::output, field, string, size)"
     )]
     pub string: String,
 }
 
+impl DekuFixedString {
+    fn write_fixed(
+        output: &mut BitVec<Msb0, u8>,
+        field: &str,
+        string: &str,
+        size: usize,
+    ) -> Result<(), DekuError> {
+        let mut bytes = fixed_string_bytes(field, string, size)?;
+        bytes.resize(size, 0);
+        bytes.write(output, ())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverflowPolicy {
+    #[default]
+    Error,
+    Truncate,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FixedStringOverflow {
+    pub field: String,
+    pub limit: usize,
+
+    /// Length of the string in bytes, after encoding
+    pub length: usize,
+}
+
+impl fmt::Display for FixedStringOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is {} bytes long, exceeding the {} byte limit",
+            self.field, self.length, self.limit
+        )
+    }
+}
+
+impl std::error::Error for FixedStringOverflow {}
+
+impl From<FixedStringOverflow> for DekuError {
+    fn from(overflow: FixedStringOverflow) -> Self {
+        DekuError::InvalidParam(overflow.to_string())
+    }
+}
+
+/// Encodes a string for a fixed-size field, failing if it does not fit in `limit` bytes.
+pub fn fixed_string_bytes(
+    field: &str,
+    string: &str,
+    limit: usize,
+) -> Result<Vec<u8>, FixedStringOverflow> {
+    if string.len() > limit {
+        return Err(FixedStringOverflow {
+            field: field.to_string(),
+            limit,
+            length: string.len(),
+        });
+    }
+
+    Ok(string.as_bytes().to_vec())
+}
+
+/// Makes a string fit in a fixed-size field according to `policy`.
+/// Returns the overflow if the string was truncated.
+pub fn fit_fixed_string(
+    field: &str,
+    string: &mut String,
+    limit: usize,
+    policy: OverflowPolicy,
+) -> Result<Option<FixedStringOverflow>, FixedStringOverflow> {
+    let overflow = match fixed_string_bytes(field, string, limit) {
+        Ok(_) => return Ok(None),
+        Err(overflow) => overflow,
+    };
+
+    match policy {
+        OverflowPolicy::Error => Err(overflow),
+        OverflowPolicy::Truncate => {
+            // Never cut a character in half
+            let mut end = limit;
+            while !string.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            string.truncate(end);
+            Ok(Some(overflow))
+        }
+    }
+}
+
 impl Serialize for DekuFixedString {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
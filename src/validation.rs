@@ -0,0 +1,424 @@
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::Hash;
+
+use crate::nucc_binary_parsed::calc_crc32;
+use crate::{
+    CharaCode, CharaCodeMap, EvFile, MessageInfo, NuccBinaryParsed, PlayerColorParam, PrmLoad,
+    SoundTestParam, StageInfo,
+};
+
+/// A set of parsed files, along with the paths of other files that can be referenced by them.
+#[derive(Default)]
+pub struct NuccBinaryParsedSet {
+    pub files: Vec<(String, Box<dyn NuccBinaryParsed>)>,
+    pub known_paths: Vec<String>,
+}
+
+impl NuccBinaryParsedSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: &str, parsed: Box<dyn NuccBinaryParsed>) {
+        self.files.push((path.to_string(), parsed));
+    }
+
+    pub fn add_known_path(&mut self, path: &str) {
+        self.known_paths.push(path.to_string());
+    }
+
+    pub fn files_of<T: NuccBinaryParsed>(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.files
+            .iter()
+            .filter_map(|(path, parsed)| Some((path.as_str(), parsed.downcast_ref::<T>()?)))
+    }
+
+    pub fn files_of_mut<T: NuccBinaryParsed>(&mut self) -> impl Iterator<Item = (&str, &mut T)> {
+        self.files
+            .iter_mut()
+            .filter_map(|(path, parsed)| Some((path.as_str(), parsed.downcast_mut::<T>()?)))
+    }
+
+    /// Finds references to message IDs, character codes and file paths that do not exist in the set.
+    /// Each kind of reference is only checked if the set contains something it can be resolved against.
+    pub fn dangling_references(&self) -> Vec<DanglingReference> {
+        let mut dangling = Vec::new();
+
+        let message_ids: HashSet<&[u8]> = self
+            .files_of::<MessageInfo>()
+            .flat_map(|(_, message_info)| message_info.entries.iter())
+            .map(|entry| entry.msg_id_crc32.as_slice())
+            .collect();
+
+        if !message_ids.is_empty() {
+            for (path, sound_test) in self.files_of::<SoundTestParam>() {
+                for (i, entry) in sound_test.entries.iter().enumerate() {
+                    for (field, msg_id) in
+                        [("name_id", &entry.name_id), ("desc_id", &entry.desc_id)]
+                    {
+                        if !msg_id.is_empty()
+                            && !message_ids.contains(calc_crc32(msg_id.as_bytes()).as_slice())
+                        {
+                            dangling.push(DanglingReference::new(
+                                ReferenceKind::MessageId,
+                                msg_id,
+                                path,
+                                format!("entries[{}].{}", i, field),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut chara_codes = CharaCodeMap::default();
+        for (_, chara_code) in self.files_of::<CharaCode>() {
+            chara_codes.extend(chara_code);
+        }
+
+        if !chara_codes.is_empty() {
+            for (path, _) in self.files_of::<PrmLoad>() {
+                if let Some(code) = PrmLoad::chara_code_of_path(path) {
+                    if !chara_codes.contains_code(code) {
+                        dangling.push(DanglingReference::new(
+                            ReferenceKind::CharaCode,
+                            code,
+                            path,
+                            String::from("(file name)"),
+                        ));
+                    }
+                }
+            }
+
+            for (path, color_param) in self.files_of::<PlayerColorParam>() {
+                for i in color_param.unknown_chara_codes(&chara_codes) {
+                    dangling.push(DanglingReference::new(
+                        ReferenceKind::CharaCode,
+                        &color_param.entries[i].char_code,
+                        path,
+                        format!("entries[{}].char_code", i),
+                    ));
+                }
+            }
+        }
+
+        if !self.known_paths.is_empty() {
+            let known_paths: Vec<String> = self
+                .known_paths
+                .iter()
+                .map(|path| normalize_path(path))
+                .collect();
+
+            let mut check_path = |reference: &str, path: &str, location: String| {
+                if !reference.is_empty() && !resolves_path(&known_paths, reference) {
+                    dangling.push(DanglingReference::new(
+                        ReferenceKind::FilePath,
+                        reference,
+                        path,
+                        location,
+                    ));
+                }
+            };
+
+            for (path, stage_info) in self.files_of::<StageInfo>() {
+                for (i, entry) in stage_info.entries.iter().enumerate() {
+                    for (j, xfbin_path) in entry.xfbin_paths.iter().enumerate() {
+                        check_path(
+                            xfbin_path,
+                            path,
+                            format!("entries[{}].xfbin_paths[{}]", i, j),
+                        );
+                    }
+
+                    for (j, prop) in entry.props.iter().enumerate() {
+                        check_path(
+                            &prop.xfbin_path,
+                            path,
+                            format!("entries[{}].props[{}].xfbin_path", i, j),
+                        );
+                    }
+                }
+            }
+
+            for (path, ev) in self.files_of::<EvFile>() {
+                for (i, entry) in ev.entries.iter().enumerate() {
+                    check_path(
+                        &entry.xfbin_path,
+                        path,
+                        format!("entries[{}].xfbin_path", i),
+                    );
+                }
+            }
+
+            for (path, prm_load) in self.files_of::<PrmLoad>() {
+                for (i, entry) in prm_load.entries.iter().enumerate() {
+                    check_path(
+                        &format!("{}/{}", entry.folder_name.string, entry.file_name.string),
+                        path,
+                        format!("entries[{}]", i),
+                    );
+                }
+            }
+        }
+
+        dangling
+    }
+
+    /// Runs the lints of every file, along with the cross-file reference checks.
+    pub fn validate(&self) -> Report {
+        let mut report = Report::default();
+
+        for (path, parsed) in self.files.iter() {
+            report.add(path, parsed.validate());
+        }
+
+        for reference in self.dangling_references() {
+            let file = reference.file.clone();
+            report.add(&file, vec![reference.into()]);
+        }
+
+        report
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/").to_lowercase()
+}
+
+/// A reference resolves if a known path ends with it, with or without the known path's extension.
+fn resolves_path(known_paths: &[String], reference: &str) -> bool {
+    let reference = normalize_path(reference);
+    let reference = reference.trim_start_matches('/');
+
+    let matches = |path: &str| {
+        path == reference
+            || (path.ends_with(reference) && path[..path.len() - reference.len()].ends_with('/'))
+    };
+
+    known_paths.iter().any(|path| {
+        matches(path)
+            || path
+                .rfind('.')
+                .filter(|&dot| !path[dot..].contains('/'))
+                .is_some_and(|dot| matches(&path[..dot]))
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReferenceKind {
+    MessageId,
+    CharaCode,
+    FilePath,
+}
+
+impl fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ReferenceKind::MessageId => "message ID",
+            ReferenceKind::CharaCode => "character code",
+            ReferenceKind::FilePath => "file path",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DanglingReference {
+    pub kind: ReferenceKind,
+    pub value: String,
+
+    /// Path of the file containing the reference
+    pub file: String,
+
+    /// JSON path of the referencing field, relative to the file
+    pub location: String,
+}
+
+impl DanglingReference {
+    fn new(kind: ReferenceKind, value: &str, file: &str, location: String) -> Self {
+        Self {
+            kind,
+            value: value.to_string(),
+            file: file.to_string(),
+            location,
+        }
+    }
+}
+
+impl fmt::Display for DanglingReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {} `{}` does not exist",
+            self.file, self.location, self.kind, self.value
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// JSON path of the offending field
+    pub path: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(path: String, message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            path,
+            message,
+        }
+    }
+
+    pub fn warning(path: String, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            path,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.path, self.message)
+    }
+}
+
+impl From<DanglingReference> for Diagnostic {
+    fn from(reference: DanglingReference) -> Self {
+        Diagnostic::error(
+            reference.location,
+            format!("{} `{}` does not exist", reference.kind, reference.value),
+        )
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReportEntry {
+    pub file: String,
+
+    #[serde(flatten)]
+    pub diagnostic: Diagnostic,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Report {
+    pub entries: Vec<ReportEntry>,
+}
+
+impl Report {
+    pub fn add(&mut self, file: &str, diagnostics: Vec<Diagnostic>) {
+        self.entries
+            .extend(diagnostics.into_iter().map(|diagnostic| ReportEntry {
+                file: file.to_string(),
+                diagnostic,
+            }));
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.diagnostic.severity == severity)
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.count(Severity::Error) != 0
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(
+                f,
+                "{}: {}: {}: {}",
+                entry.diagnostic.severity,
+                entry.file,
+                entry.diagnostic.path,
+                entry.diagnostic.message
+            )?;
+        }
+
+        write!(
+            f,
+            "{} error(s), {} warning(s)",
+            self.count(Severity::Error),
+            self.count(Severity::Warning)
+        )
+    }
+}
+
+/// Returns `(index, first_index)` for every key that already appeared at `first_index`.
+pub(crate) fn find_duplicates<K: Eq + Hash>(
+    keys: impl IntoIterator<Item = K>,
+) -> Vec<(usize, usize)> {
+    let mut seen = HashMap::new();
+    let mut duplicates = Vec::new();
+
+    for (i, key) in keys.into_iter().enumerate() {
+        match seen.get(&key) {
+            Some(&first) => duplicates.push((i, first)),
+            None => {
+                seen.insert(key, i);
+            }
+        }
+    }
+
+    duplicates
+}
+
+pub(crate) fn check_string(
+    diagnostics: &mut Vec<Diagnostic>,
+    path: String,
+    string: &str,
+    max_len: usize,
+) {
+    if string.is_empty() {
+        diagnostics.push(Diagnostic::warning(path, String::from("string is empty")));
+    } else if string.len() > max_len {
+        diagnostics.push(Diagnostic::error(
+            path,
+            format!(
+                "string is {} bytes long, exceeding the {} byte limit",
+                string.len(),
+                max_len
+            ),
+        ));
+    }
+}
+
+pub(crate) fn check_float(diagnostics: &mut Vec<Diagnostic>, path: String, value: f32) {
+    if !value.is_finite() {
+        diagnostics.push(Diagnostic::error(
+            path,
+            format!("{} is not a finite number", value),
+        ));
+    }
+}
+
+pub(crate) fn check_floats(diagnostics: &mut Vec<Diagnostic>, path: String, values: &[f32]) {
+    for (i, value) in values.iter().enumerate() {
+        check_float(diagnostics, format!("{}[{}]", path, i), *value);
+    }
+}
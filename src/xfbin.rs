@@ -0,0 +1,600 @@
+use deku::ctx::Endian;
+use encoding_rs::SHIFT_JIS;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use strum::IntoEnumIterator;
+
+use crate::{EvVersion, NuccBinaryParsed, NuccBinaryParsedReader, NuccBinaryType};
+
+pub const XFBIN_MAGIC: &[u8] = b"NUCC";
+
+pub const CHUNK_NULL: &str = "nuccChunkNull";
+pub const CHUNK_PAGE: &str = "nuccChunkPage";
+pub const CHUNK_BINARY: &str = "nuccChunkBinary";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XfbinHeader {
+    pub version: u32,
+    pub reserved: u64,
+    pub chunk_table_size: u32,
+    pub min_page_size: u32,
+    pub chunk_table_version: u16,
+    pub unknown: u16,
+}
+
+impl XfbinHeader {
+    pub const SIZE: usize = 0x1C;
+}
+
+/// Indices into the chunk type, file path and chunk name tables
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XfbinChunkMap {
+    pub chunk_type: u32,
+    pub file_path: u32,
+    pub chunk_name: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XfbinChunkTable {
+    pub chunk_types: Vec<String>,
+    pub file_paths: Vec<String>,
+    pub chunk_names: Vec<String>,
+    pub chunk_maps: Vec<XfbinChunkMap>,
+
+    /// Written back as read, since chunk maps are never added or removed
+    pub chunk_map_size: u32,
+
+    /// Pairs of chunk map indices, used by chunks that reference other chunks
+    pub extra_indices: Vec<(u32, u32)>,
+
+    /// Chunk map of every chunk, relative to the start of its page
+    pub chunk_map_indices: Vec<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XfbinChunk {
+    /// Offset of the chunk header from the start of the file
+    pub offset: usize,
+
+    /// Index into the page's slice of `chunk_map_indices`
+    pub map_index: u32,
+    pub version: u16,
+    pub unknown: u16,
+
+    pub chunk_type: String,
+    pub file_path: String,
+    pub name: String,
+
+    #[serde(with = "hex::serde")]
+    pub data: Vec<u8>,
+}
+
+impl XfbinChunk {
+    pub const HEADER_SIZE: usize = 12;
+
+    /// Payload of a `nuccChunkBinary`, without its size prefix.
+    pub fn binary_payload(&self) -> Option<&[u8]> {
+        if self.chunk_type != CHUNK_BINARY {
+            return None;
+        }
+
+        let size = u32::from_be_bytes(self.data.get(..4)?.try_into().unwrap()) as usize;
+        self.data.get(4..4 + size)
+    }
+
+    /// Sets the data of a `nuccChunkBinary` to a raw payload, adding its size prefix.
+    pub fn set_binary_payload(&mut self, payload: &[u8]) {
+        self.data = wrap_binary_chunk(payload, Endian::Big);
+    }
+
+    fn write(&self, output: &mut Vec<u8>) {
+        output.extend((self.data.len() as u32).to_be_bytes());
+        output.extend(self.map_index.to_be_bytes());
+        output.extend(self.version.to_be_bytes());
+        output.extend(self.unknown.to_be_bytes());
+        output.extend_from_slice(&self.data);
+    }
+}
+
+/// Chunks from a `nuccChunkNull` up to and including the `nuccChunkPage` that ends the page.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct XfbinPage {
+    /// Start of the page in `chunk_map_indices`
+    pub map_index_offset: u32,
+
+    /// Start of the page in `extra_indices`
+    pub extra_index_offset: u32,
+    pub chunks: Vec<XfbinChunk>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum XfbinError {
+    InvalidMagic,
+    Truncated { offset: usize },
+    InvalidChunkMap { offset: usize, index: u32 },
+    InvalidTableIndex { table: &'static str, index: u32 },
+    ChunkNotFound(String),
+    InvalidSizePrefix { size: Option<usize>, length: usize },
+    InvalidPage { offset: usize },
+    InvalidPayload { path: String, message: String },
+}
+
+impl fmt::Display for XfbinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XfbinError::InvalidMagic => write!(f, "file does not start with NUCC"),
+            XfbinError::Truncated { offset } => {
+                write!(f, "file is truncated at offset {:#X}", offset)
+            }
+            XfbinError::InvalidChunkMap { offset, index } => write!(
+                f,
+                "chunk at offset {:#X} refers to chunk map index {} which does not exist",
+                offset, index
+            ),
+            XfbinError::InvalidTableIndex { table, index } => {
+                write!(
+                    f,
+                    "chunk map refers to {} {} which does not exist",
+                    table, index
+                )
+            }
+            XfbinError::ChunkNotFound(key) => {
+                write!(f, "no binary chunk has the path or name {:?}", key)
+            }
+            XfbinError::InvalidSizePrefix { size: None, length } => write!(
+                f,
+                "binary chunk data of {} bytes is too short for a size prefix",
+                length
+            ),
+            XfbinError::InvalidSizePrefix {
+                size: Some(size),
+                length,
+            } => write!(
+                f,
+                "binary chunk size prefix is {} but {} bytes follow it",
+                size,
+                length - 4
+            ),
+            XfbinError::InvalidPage { offset } => write!(
+                f,
+                "page chunk at offset {:#X} has more indices than the file can hold",
+                offset
+            ),
+            XfbinError::InvalidPayload { path, message } => {
+                write!(f, "binary chunk {:?} could not be read: {}", path, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for XfbinError {}
+
+/// A `nuccChunkBinary` along with the file type matched from its path.
+pub struct XfbinBinaryChunk<'a> {
+    pub chunk: &'a XfbinChunk,
+    pub payload: &'a [u8],
+
+    /// `None` if the path does not match any of the supported types
+    pub binary_type: Option<(NuccBinaryType, Endian)>,
+}
+
+impl XfbinBinaryChunk<'_> {
+    /// Reads the payload as its matched type, or returns `None` if the path did not match any.
+    /// Ev payloads are read with `EvVersion::Auto`, since the container does not say which game they are from.
+    pub fn parse(&self) -> Result<Option<Box<dyn NuccBinaryParsed>>, XfbinError> {
+        let (binary_type, endian) = match self.binary_type {
+            Some(binary_type) => binary_type,
+            None => return Ok(None),
+        };
+
+        let version = match binary_type {
+            NuccBinaryType::Ev(_) => EvVersion::iter()
+                .position(|version| version == EvVersion::Auto)
+                .unwrap(),
+            _ => 0,
+        };
+
+        NuccBinaryParsedReader(binary_type, self.payload, endian, version)
+            .try_read()
+            .map(Some)
+            .map_err(|error| XfbinError::InvalidPayload {
+                path: self.chunk.file_path.clone(),
+                message: error.to_string(),
+            })
+    }
+}
+
+fn read_size(data: &[u8], endian: Endian) -> Option<usize> {
+    let bytes = data.get(..4)?.try_into().unwrap();
+
+    Some(match endian {
+        Endian::Big => u32::from_be_bytes(bytes),
+        Endian::Little => u32::from_le_bytes(bytes),
+    } as usize)
+}
+
+/// Reads the size prefix of `nuccChunkBinary` data in `endian`, or returns `None` if the data is too short.
+pub fn binary_chunk_prefix(data: &[u8], endian: Endian) -> Option<usize> {
+    read_size(data, endian)
+}
+
+/// Removes the size prefix of `nuccChunkBinary` data, checking that it matches the size of the payload.
+pub fn unwrap_binary_chunk(data: &[u8], endian: Endian) -> Result<&[u8], XfbinError> {
+    let size = binary_chunk_prefix(data, endian);
+
+    if size != Some(data.len().saturating_sub(4)) {
+        return Err(XfbinError::InvalidSizePrefix {
+            size,
+            length: data.len(),
+        });
+    }
+
+    Ok(&data[4..])
+}
+
+/// Prefixes a raw payload with its size in `endian`.
+pub fn wrap_binary_chunk(payload: &[u8], endian: Endian) -> Vec<u8> {
+    let size = payload.len() as u32;
+
+    let mut output = match endian {
+        Endian::Big => size.to_be_bytes(),
+        Endian::Little => size.to_le_bytes(),
+    }
+    .to_vec();
+
+    output.extend_from_slice(payload);
+    output
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, size: usize) -> Result<&'a [u8], XfbinError> {
+        let bytes =
+            self.data
+                .get(self.position..self.position + size)
+                .ok_or(XfbinError::Truncated {
+                    offset: self.position,
+                })?;
+
+        self.position += size;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, XfbinError> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, XfbinError> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, XfbinError> {
+        Ok(u64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Reads `count` null terminated strings from a table of `size` bytes
+    fn strings(&mut self, count: u32, size: u32) -> Result<Vec<String>, XfbinError> {
+        let table = self.bytes(size as usize)?;
+
+        Ok(table
+            .split(|b| *b == 0)
+            .take(count as usize)
+            .map(|string| SHIFT_JIS.decode_without_bom_handling(string).0.into_owned())
+            .collect())
+    }
+}
+
+fn write_strings(strings: &[String]) -> Vec<u8> {
+    let mut table = Vec::new();
+    for string in strings {
+        table.extend_from_slice(&SHIFT_JIS.encode(string).0);
+        table.push(0);
+    }
+
+    table
+}
+
+impl XfbinChunkTable {
+    fn write(&self, output: &mut Vec<u8>) {
+        let chunk_types = write_strings(&self.chunk_types);
+        let file_paths = write_strings(&self.file_paths);
+        let chunk_names = write_strings(&self.chunk_names);
+
+        for value in [
+            self.chunk_types.len() as u32,
+            chunk_types.len() as u32,
+            self.file_paths.len() as u32,
+            file_paths.len() as u32,
+            self.chunk_names.len() as u32,
+            chunk_names.len() as u32,
+            self.chunk_maps.len() as u32,
+            self.chunk_map_size,
+            self.chunk_map_indices.len() as u32,
+            self.extra_indices.len() as u32,
+        ] {
+            output.extend(value.to_be_bytes());
+        }
+
+        output.extend(chunk_types);
+        output.extend(file_paths);
+        output.extend(chunk_names);
+        output.resize(output.len().next_multiple_of(4), 0);
+
+        for chunk_map in self.chunk_maps.iter() {
+            output.extend(chunk_map.chunk_type.to_be_bytes());
+            output.extend(chunk_map.file_path.to_be_bytes());
+            output.extend(chunk_map.chunk_name.to_be_bytes());
+        }
+
+        for (first, second) in self.extra_indices.iter() {
+            output.extend(first.to_be_bytes());
+            output.extend(second.to_be_bytes());
+        }
+
+        for index in self.chunk_map_indices.iter() {
+            output.extend(index.to_be_bytes());
+        }
+    }
+}
+
+fn table_entry<'a>(
+    table: &'a [String],
+    name: &'static str,
+    index: u32,
+) -> Result<&'a str, XfbinError> {
+    table
+        .get(index as usize)
+        .map(String::as_str)
+        .ok_or(XfbinError::InvalidTableIndex { table: name, index })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Xfbin {
+    pub header: XfbinHeader,
+    pub table: XfbinChunkTable,
+    pub pages: Vec<XfbinPage>,
+
+    /// The table as it was read along with its bytes, which are written back if it was not changed
+    #[serde(skip)]
+    original_table: Option<(XfbinChunkTable, Vec<u8>)>,
+}
+
+impl Xfbin {
+    pub fn read(data: &[u8]) -> Result<Self, XfbinError> {
+        if !data.starts_with(XFBIN_MAGIC) {
+            return Err(XfbinError::InvalidMagic);
+        }
+
+        let mut reader = Reader {
+            data,
+            position: XFBIN_MAGIC.len(),
+        };
+
+        let header = XfbinHeader {
+            version: reader.u32()?,
+            reserved: reader.u64()?,
+            chunk_table_size: reader.u32()?,
+            min_page_size: reader.u32()?,
+            chunk_table_version: reader.u16()?,
+            unknown: reader.u16()?,
+        };
+
+        let table = Self::read_table(&mut reader)?;
+
+        let table_end = XfbinHeader::SIZE + header.chunk_table_size as usize;
+        let original_table = data
+            .get(XfbinHeader::SIZE..table_end)
+            .map(|bytes| (table.clone(), bytes.to_vec()));
+
+        let pages = Self::read_pages(
+            &mut Reader {
+                data,
+                position: XfbinHeader::SIZE + header.chunk_table_size as usize,
+            },
+            &table,
+        )?;
+
+        Ok(Self {
+            header,
+            table,
+            pages,
+            original_table,
+        })
+    }
+
+    fn read_table(reader: &mut Reader) -> Result<XfbinChunkTable, XfbinError> {
+        let chunk_type_count = reader.u32()?;
+        let chunk_type_size = reader.u32()?;
+        let file_path_count = reader.u32()?;
+        let file_path_size = reader.u32()?;
+        let chunk_name_count = reader.u32()?;
+        let chunk_name_size = reader.u32()?;
+        let chunk_map_count = reader.u32()?;
+        let chunk_map_size = reader.u32()?;
+        let chunk_map_indices_count = reader.u32()?;
+        let extra_indices_count = reader.u32()?;
+
+        let chunk_types = reader.strings(chunk_type_count, chunk_type_size)?;
+        let file_paths = reader.strings(file_path_count, file_path_size)?;
+        let chunk_names = reader.strings(chunk_name_count, chunk_name_size)?;
+
+        reader.position = reader.position.next_multiple_of(4);
+
+        let mut chunk_maps = Vec::new();
+        for _ in 0..chunk_map_count {
+            chunk_maps.push(XfbinChunkMap {
+                chunk_type: reader.u32()?,
+                file_path: reader.u32()?,
+                chunk_name: reader.u32()?,
+            });
+        }
+
+        let mut extra_indices = Vec::new();
+        for _ in 0..extra_indices_count {
+            extra_indices.push((reader.u32()?, reader.u32()?));
+        }
+
+        let mut chunk_map_indices = Vec::new();
+        for _ in 0..chunk_map_indices_count {
+            chunk_map_indices.push(reader.u32()?);
+        }
+
+        Ok(XfbinChunkTable {
+            chunk_types,
+            file_paths,
+            chunk_names,
+            chunk_maps,
+            chunk_map_size,
+            extra_indices,
+            chunk_map_indices,
+        })
+    }
+
+    fn read_pages(
+        reader: &mut Reader,
+        table: &XfbinChunkTable,
+    ) -> Result<Vec<XfbinPage>, XfbinError> {
+        let mut pages = Vec::new();
+        let mut page = XfbinPage::default();
+
+        while reader.position < reader.data.len() {
+            let offset = reader.position;
+            let size = reader.u32()?;
+            let map_index = reader.u32()?;
+            let version = reader.u16()?;
+            let unknown = reader.u16()?;
+            let data = reader.bytes(size as usize)?.to_vec();
+
+            let chunk_map = page
+                .map_index_offset
+                .checked_add(map_index)
+                .and_then(|index| table.chunk_map_indices.get(index as usize))
+                .and_then(|index| table.chunk_maps.get(*index as usize))
+                .ok_or(XfbinError::InvalidChunkMap {
+                    offset,
+                    index: map_index,
+                })?;
+
+            let chunk = XfbinChunk {
+                offset,
+                map_index,
+                version,
+                unknown,
+                chunk_type: table_entry(&table.chunk_types, "chunk type", chunk_map.chunk_type)?
+                    .to_string(),
+                file_path: table_entry(&table.file_paths, "file path", chunk_map.file_path)?
+                    .to_string(),
+                name: table_entry(&table.chunk_names, "chunk name", chunk_map.chunk_name)?
+                    .to_string(),
+                data,
+            };
+
+            if chunk.chunk_type != CHUNK_PAGE {
+                page.chunks.push(chunk);
+                continue;
+            }
+
+            // The page chunk holds how many map indices and extra indices the page used
+            let mut page_reader = Reader {
+                data: &chunk.data,
+                position: 0,
+            };
+
+            let (page_size, extra_indices_size) = page_reader
+                .u32()
+                .and_then(|page_size| Ok((page_size, page_reader.u32()?)))
+                .map_err(|_| XfbinError::Truncated { offset })?;
+
+            let next = XfbinPage {
+                map_index_offset: page
+                    .map_index_offset
+                    .checked_add(page_size)
+                    .ok_or(XfbinError::InvalidPage { offset })?,
+                extra_index_offset: page
+                    .extra_index_offset
+                    .checked_add(extra_indices_size)
+                    .ok_or(XfbinError::InvalidPage { offset })?,
+                chunks: Vec::new(),
+            };
+
+            page.chunks.push(chunk);
+            pages.push(std::mem::replace(&mut page, next));
+        }
+
+        if !page.chunks.is_empty() {
+            pages.push(page);
+        }
+
+        Ok(pages)
+    }
+
+    /// Writes the container, recomputing chunk sizes, and the chunk table if it was changed.
+    /// The header's `chunk_table_size` is updated. Chunk offsets are not, read the output again to get them.
+    pub fn write(&mut self) -> Vec<u8> {
+        // The table is aligned relative to the start of the file, so it is written after the header
+        let mut output = XFBIN_MAGIC.to_vec();
+        output.extend(self.header.version.to_be_bytes());
+        output.extend(self.header.reserved.to_be_bytes());
+        output.extend([0; 4]);
+        output.extend(self.header.min_page_size.to_be_bytes());
+        output.extend(self.header.chunk_table_version.to_be_bytes());
+        output.extend(self.header.unknown.to_be_bytes());
+
+        match &self.original_table {
+            Some((table, bytes)) if *table == self.table => output.extend_from_slice(bytes),
+            _ => self.table.write(&mut output),
+        }
+
+        self.header.chunk_table_size = (output.len() - XfbinHeader::SIZE) as u32;
+        output[0x10..0x14].copy_from_slice(&self.header.chunk_table_size.to_be_bytes());
+
+        for chunk in self.chunks() {
+            chunk.write(&mut output);
+        }
+
+        output
+    }
+
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item = &mut XfbinChunk> {
+        self.pages
+            .iter_mut()
+            .flat_map(|page| page.chunks.iter_mut())
+    }
+
+    /// Replaces the payload of every `nuccChunkBinary` whose file path or chunk name is `key`
+    /// with the raw `payload`, returning how many were replaced.
+    pub fn replace_binary(&mut self, key: &str, payload: &[u8]) -> Result<usize, XfbinError> {
+        let mut count = 0;
+        for chunk in self.chunks_mut().filter(|chunk| {
+            chunk.chunk_type == CHUNK_BINARY && (chunk.file_path == key || chunk.name == key)
+        }) {
+            chunk.set_binary_payload(payload);
+            count += 1;
+        }
+
+        if count == 0 {
+            return Err(XfbinError::ChunkNotFound(key.to_string()));
+        }
+
+        Ok(count)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &XfbinChunk> {
+        self.pages.iter().flat_map(|page| page.chunks.iter())
+    }
+
+    /// Every `nuccChunkBinary` in the file, with its path matched against the supported types.
+    pub fn binary_chunks(&self) -> impl Iterator<Item = XfbinBinaryChunk<'_>> {
+        self.chunks().filter_map(|chunk| {
+            Some(XfbinBinaryChunk {
+                chunk,
+                payload: chunk.binary_payload()?,
+                binary_type: NuccBinaryType::match_path(&chunk.file_path),
+            })
+        })
+    }
+}